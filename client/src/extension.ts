@@ -3,18 +3,77 @@
  * Licensed under the MIT License. See License.txt in the project root for license information.
  * ------------------------------------------------------------------------------------------ */
 
-import { workspace, ExtensionContext, window } from "vscode";
+import {
+  commands,
+  workspace,
+  ExtensionContext,
+  Location,
+  Position,
+  Range,
+  SnippetString,
+  Uri,
+  window,
+} from "vscode";
 
 import {
+  ClientCapabilities,
   Executable,
+  FeatureState,
   LanguageClient,
   LanguageClientOptions,
   ServerOptions,
+  StaticFeature,
 } from "vscode-languageclient/node";
 
 let client: LanguageClient;
 
-export async function activate(_context: ExtensionContext) {
+type LspPosition = { line: number; character: number };
+type LspRange = { start: LspPosition; end: LspPosition };
+
+// The client-side commands below, announced to the server under `experimental.commands`
+const clientCommands = ["l.applySnippet", "l.showReferences"];
+
+const clientCommandsFeature: StaticFeature = {
+  fillClientCapabilities(capabilities: ClientCapabilities) {
+    capabilities.experimental = { ...capabilities.experimental, commands: clientCommands };
+  },
+  initialize() {},
+  getState(): FeatureState {
+    return { kind: "static" };
+  },
+  clear() {},
+};
+
+function toPosition(position: LspPosition): Position {
+  return new Position(position.line, position.character);
+}
+
+function toRange(range: LspRange): Range {
+  return new Range(range.start.line, range.start.character, range.end.line, range.end.character);
+}
+
+export async function activate(context: ExtensionContext) {
+  // Used by refactorings that introduce a new name, so the user can pick it right away
+  context.subscriptions.push(
+    commands.registerCommand("l.applySnippet", async (uri: string, range: LspRange, snippet: string) => {
+      const editor = await window.showTextDocument(Uri.parse(uri));
+      await editor.insertSnippet(new SnippetString(snippet), toRange(range));
+    }),
+  );
+  // Used by the reference count lenses: `editor.action.showReferences` takes VS Code objects, not JSON
+  context.subscriptions.push(
+    commands.registerCommand(
+      "l.showReferences",
+      async (uri: string, position: LspPosition, locations: { uri: string; range: LspRange }[]) => {
+        await commands.executeCommand(
+          "editor.action.showReferences",
+          Uri.parse(uri),
+          toPosition(position),
+          locations.map((location) => new Location(Uri.parse(location.uri), toRange(location.range))),
+        );
+      },
+    ),
+  );
   const traceOutputChannel = window.createOutputChannel("L Language Server trace");
   const command = process.env.SERVER_PATH || "l-language-server";
   const run: Executable = {
@@ -38,6 +97,8 @@ export async function activate(_context: ExtensionContext) {
     // Register the server for plain text documents
     documentSelector: [{ scheme: "file", language: "l" }],
     synchronize: {
+      // Send the `l-language-server` settings to the server whenever they change
+      configurationSection: "l-language-server",
       // Notify the server about file changes to '.clientrc files contained in the workspace
       fileEvents: workspace.createFileSystemWatcher("**/.clientrc"),
     },
@@ -51,6 +112,7 @@ export async function activate(_context: ExtensionContext) {
     serverOptions,
     clientOptions,
   );
+  client.registerFeature(clientCommandsFeature);
   client.start();
 }
 
@@ -0,0 +1,310 @@
+use l_lang::{CompileResult, Expr, ExprCall, Function, Item, SymbolId};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::documentation::function_signature;
+use crate::syntax::{find_function_by_name_span, walk, SyntaxNode};
+use crate::{position_to_offset, span_to_range, Backend};
+
+impl Backend {
+    pub(crate) fn prepare_call_hierarchy_items(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Option<Vec<CallHierarchyItem>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let key = uri.to_string();
+        let rope = self.document_map.get(&key)?;
+        let semantic_result = self.semanticast_map.get(&key)?;
+        let offset = position_to_offset(position, &rope)?;
+
+        let symbol_id = semantic_result.semantic.get_symbol_at(offset)?;
+        let name_span = semantic_result.semantic.get_symbol_span(symbol_id);
+        let function = find_function_by_name_span(semantic_result.program.file(), name_span)?;
+        Some(vec![call_hierarchy_item(&uri, &rope, function)?])
+    }
+
+    /// Every call to the item's function in the indexed files, grouped by calling function.
+    pub(crate) fn get_incoming_calls(
+        &self,
+        item: CallHierarchyItem,
+    ) -> Option<Vec<CallHierarchyIncomingCall>> {
+        let target_key = item.uri.to_string();
+        let target_symbol = {
+            let rope = self.document_map.get(&target_key)?;
+            let semantic_result = self.semanticast_map.get(&target_key)?;
+            let offset = position_to_offset(item.selection_range.start, &rope)?;
+            semantic_result.semantic.get_symbol_at(offset)?
+        };
+
+        let mut incoming_calls = vec![];
+        for entry in self.semanticast_map.iter() {
+            let Ok(uri) = Url::parse(entry.key()) else {
+                continue;
+            };
+            let Some(rope) = self.document_map.get(entry.key()) else {
+                continue;
+            };
+            let semantic_result = entry.value();
+            let same_file = *entry.key() == target_key;
+            for caller in functions(semantic_result) {
+                let from_ranges = calls_in(caller)
+                    .into_iter()
+                    .filter_map(|call_expr| {
+                        let callee = call_expr.callee.as_deref()?;
+                        let matches = if same_file {
+                            callee_symbol(callee, semantic_result) == Some(target_symbol)
+                        } else {
+                            callee_name(callee) == Some(item.name.as_str())
+                                && callee_symbol(callee, semantic_result).is_none()
+                        };
+                        if !matches {
+                            return None;
+                        }
+                        span_to_range(callee.span(), &rope)
+                    })
+                    .collect::<Vec<_>>();
+                if from_ranges.is_empty() {
+                    continue;
+                }
+                if let Some(from) = call_hierarchy_item(&uri, &rope, caller) {
+                    incoming_calls.push(CallHierarchyIncomingCall { from, from_ranges });
+                }
+            }
+        }
+        Some(incoming_calls)
+    }
+
+    /// Every function called from the item's function body, grouped by callee. Calls that do not
+    /// resolve in the caller's file are looked up by name in the other indexed files.
+    pub(crate) fn get_outgoing_calls(
+        &self,
+        item: CallHierarchyItem,
+    ) -> Option<Vec<CallHierarchyOutgoingCall>> {
+        let key = item.uri.to_string();
+        let rope = self.document_map.get(&key)?;
+        let semantic_result = self.semanticast_map.get(&key)?;
+        let offset = position_to_offset(item.selection_range.start, &rope)?;
+        let caller = functions(&semantic_result).find(|function| {
+            function.name.as_ref().is_some_and(|name| {
+                name.span.start as usize <= offset && offset <= name.span.end as usize
+            })
+        })?;
+
+        let mut targets = vec![];
+        let mut unresolved = vec![];
+        for call_expr in calls_in(caller) {
+            let Some(callee) = call_expr.callee.as_deref() else {
+                continue;
+            };
+            let Some(from_range) = span_to_range(callee.span(), &rope) else {
+                continue;
+            };
+            match callee_symbol(callee, &semantic_result) {
+                Some(symbol_id) => {
+                    let name_span = semantic_result.semantic.get_symbol_span(symbol_id);
+                    if let Some(to) =
+                        find_function_by_name_span(semantic_result.program.file(), name_span)
+                            .and_then(|function| call_hierarchy_item(&item.uri, &rope, function))
+                    {
+                        targets.push((to, from_range));
+                    }
+                }
+                None => {
+                    if let Some(name) = callee_name(callee) {
+                        unresolved.push((name.to_string(), from_range));
+                    }
+                }
+            }
+        }
+        // Release this document before scanning the others for unresolved callees.
+        drop(semantic_result);
+        drop(rope);
+        targets.extend(unresolved.into_iter().filter_map(|(name, from_range)| {
+            Some((self.find_function_in_other_files(&key, &name)?, from_range))
+        }));
+
+        let mut outgoing_calls: Vec<CallHierarchyOutgoingCall> = vec![];
+        for (to, from_range) in targets {
+            match outgoing_calls
+                .iter_mut()
+                .find(|call| call.to.uri == to.uri && call.to.range == to.range)
+            {
+                Some(call) => call.from_ranges.push(from_range),
+                None => outgoing_calls.push(CallHierarchyOutgoingCall {
+                    to,
+                    from_ranges: vec![from_range],
+                }),
+            }
+        }
+        Some(outgoing_calls)
+    }
+
+    fn find_function_in_other_files(&self, exclude: &str, name: &str) -> Option<CallHierarchyItem> {
+        self.semanticast_map.iter().find_map(|entry| {
+            if entry.key() == exclude {
+                return None;
+            }
+            let uri = Url::parse(entry.key()).ok()?;
+            let rope = self.document_map.get(entry.key())?;
+            let function = functions(entry.value()).find(|function| {
+                function
+                    .name
+                    .as_ref()
+                    .is_some_and(|function_name| function_name.name == name)
+            })?;
+            call_hierarchy_item(&uri, &rope, function)
+        })
+    }
+}
+
+fn call_hierarchy_item(uri: &Url, rope: &Rope, function: &Function) -> Option<CallHierarchyItem> {
+    let name = function.name.as_ref()?;
+    Some(CallHierarchyItem {
+        name: name.name.clone(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: Some(function_signature(function)),
+        uri: uri.clone(),
+        range: span_to_range(function.span, rope)?,
+        selection_range: span_to_range(name.span, rope)?,
+        data: None,
+    })
+}
+
+fn functions(semantic_result: &CompileResult) -> impl Iterator<Item = &Function> {
+    semantic_result
+        .program
+        .file()
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(function) => Some(function),
+            _ => None,
+        })
+}
+
+/// The call expressions in a function body, in source order.
+fn calls_in(function: &Function) -> Vec<&ExprCall> {
+    let mut calls = vec![];
+    walk(SyntaxNode::Function(function), &mut |node| {
+        if let SyntaxNode::Expr(Expr::Call(call_expr)) = node {
+            calls.push(call_expr);
+        }
+    });
+    calls
+}
+
+fn callee_symbol(callee: &Expr, semantic_result: &CompileResult) -> Option<SymbolId> {
+    let Expr::Name(name_expr) = callee else {
+        return None;
+    };
+    let reference_id = semantic_result
+        .semantic
+        .get_reference_at(name_expr.span.start as usize)?;
+    semantic_result.semantic.references[reference_id]
+}
+
+fn callee_name(callee: &Expr) -> Option<&str> {
+    match callee {
+        Expr::Name(name_expr) => Some(name_expr.name.as_str()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, Fixture};
+
+    const MAIN: &str = "fn $0double(x: int) -> int {\n    return x * 2;\n}\n\n\
+                        fn quad(x: int) -> int {\n    return double(double(x)) + helper(x);\n}\n";
+    const OTHER: &str = "fn helper(x: int) -> int {\n    return double(x);\n}\n";
+
+    /// The one call hierarchy item `backend` prepares at the cursor of `fixture`.
+    fn prepare(backend: &Backend, fixture: &Fixture) -> CallHierarchyItem {
+        let mut items = backend
+            .prepare_call_hierarchy_items(CallHierarchyPrepareParams {
+                text_document_position_params: fixture.position_params(),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        items.remove(0)
+    }
+
+    #[test]
+    fn prepares_the_function_under_the_cursor() {
+        let fixture = Fixture::new(MAIN);
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        let item = prepare(service.inner(), &fixture);
+        assert_eq!(item.name, "double");
+        assert_eq!(item.detail.as_deref(), Some("fn double(x: int) -> int"));
+        assert_eq!(item.range.start, Position::new(0, 0));
+        assert_eq!(item.selection_range.start, Position::new(0, 3));
+    }
+
+    #[test]
+    fn finds_incoming_calls_in_every_document() {
+        let fixture = Fixture::new(MAIN);
+        let (service, _socket) = backend(&fixture.with_others(&[("file:///other.l", OTHER)]));
+        let backend = service.inner();
+        let item = prepare(backend, &fixture);
+        let mut calls = backend
+            .get_incoming_calls(item)
+            .unwrap()
+            .into_iter()
+            .map(|call| {
+                let starts = call
+                    .from_ranges
+                    .iter()
+                    .map(|range| range.start.character)
+                    .collect::<Vec<_>>();
+                (call.from.uri.to_string(), call.from.name, starts)
+            })
+            .collect::<Vec<_>>();
+        calls.sort();
+        assert_eq!(
+            calls,
+            [
+                (
+                    "file:///main.l".to_string(),
+                    "quad".to_string(),
+                    vec![11, 18]
+                ),
+                (
+                    "file:///other.l".to_string(),
+                    "helper".to_string(),
+                    vec![11]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_outgoing_calls_grouped_by_callee() {
+        let fixture = Fixture::new(&MAIN.replace("$0", "").replace("fn quad", "fn $0quad"));
+        let (service, _socket) = backend(&fixture.with_others(&[("file:///other.l", OTHER)]));
+        let backend = service.inner();
+        let item = prepare(backend, &fixture);
+        let calls = backend
+            .get_outgoing_calls(item)
+            .unwrap()
+            .into_iter()
+            .map(|call| {
+                (
+                    call.to.uri.to_string(),
+                    call.to.name,
+                    call.from_ranges.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                ("file:///main.l".to_string(), "double".to_string(), 2),
+                ("file:///other.l".to_string(), "helper".to_string(), 1),
+            ]
+        );
+    }
+}
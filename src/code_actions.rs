@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+
+use l_lang::{CompileResult, Expr, ExprCall, File, Span, Stmt};
+use ropey::Rope;
+use serde_json::json;
+use tower_lsp::lsp_types::*;
+
+use crate::syntax::{ancestors_of_span, walk, SyntaxNode};
+use crate::{offset_to_position, position_to_offset, span_text, span_to_range, Backend};
+
+mod annotate_types;
+mod change_signature;
+mod extract_function;
+mod extract_variable;
+mod generate_helpers;
+mod generate_missing;
+mod inline_function;
+mod inline_variable;
+mod parameter_object;
+mod reorder_fields;
+
+pub use change_signature::CHANGE_SIGNATURE_COMMAND;
+
+/// What every code action provider gets to look at: the document and the selected span, with
+/// surrounding whitespace trimmed off the selection.
+pub struct ActionContext<'a> {
+    pub uri: &'a Url,
+    pub semantic_result: &'a CompileResult,
+    pub rope: &'a Rope,
+    pub selection: Span,
+    /// Whether the client provides [`APPLY_SNIPPET_COMMAND`].
+    pub snippets: bool,
+}
+
+impl Backend {
+    pub(crate) fn get_code_actions(&self, params: CodeActionParams) -> Option<CodeActionResponse> {
+        let uri = &params.text_document.uri;
+        let key = uri.to_string();
+        let (mut actions, selection) = {
+            let semantic_result = self.semanticast_map.get(&key)?;
+            let rope = self.document_map.get(&key)?;
+            let start = position_to_offset(params.range.start, &rope)?;
+            let end = position_to_offset(params.range.end, &rope)?;
+            let ctx = ActionContext {
+                uri,
+                semantic_result: &semantic_result,
+                rope: &rope,
+                selection: trim_selection(start as u32, end as u32, &rope),
+                snippets: self.supports_client_command(APPLY_SNIPPET_COMMAND),
+            };
+
+            let mut actions = vec![];
+            actions.extend(extract_variable::actions(&ctx));
+            actions.extend(extract_function::actions(&ctx));
+            actions.extend(inline_variable::actions(&ctx));
+            actions.extend(inline_function::actions(&ctx));
+            actions.extend(generate_missing::actions(&ctx));
+            actions.extend(generate_helpers::actions(&ctx));
+            actions.extend(annotate_types::actions(&ctx));
+            (actions, ctx.selection)
+        };
+        // These look at other documents, so this one's entries must be released first.
+        actions.extend(self.inline_all_action(uri, selection.start));
+        actions.extend(self.parameter_object_action(uri, selection));
+        actions.extend(self.reorder_fields_actions(uri, selection));
+
+        let only = params.context.only.unwrap_or_default();
+        Some(
+            actions
+                .into_iter()
+                .filter(|action| {
+                    only.is_empty()
+                        || action.kind.as_ref().is_some_and(|kind| {
+                            only.iter().any(|only| {
+                                kind.as_str() == only.as_str()
+                                    || kind.as_str().starts_with(&format!("{}.", only.as_str()))
+                            })
+                        })
+                })
+                .map(CodeActionOrCommand::CodeAction)
+                .collect(),
+        )
+    }
+
+    /// Calls `visit` with every open document other than `key` that uses `name` without resolving
+    /// it, which is how uses of an item declared in `key` show up there, along with the spans of
+    /// those uses. Stops at the first error `visit` returns.
+    fn try_for_each_use_elsewhere<E>(
+        &self,
+        key: &str,
+        name: &str,
+        mut visit: impl FnMut(Url, &CompileResult, &Rope, Vec<Span>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for entry in self.semanticast_map.iter() {
+            if *entry.key() == key {
+                continue;
+            }
+            let (Ok(uri), Some(rope)) =
+                (Url::parse(entry.key()), self.document_map.get(entry.key()))
+            else {
+                continue;
+            };
+            let spans = unresolved_references_named(entry.value(), &rope, name);
+            if !spans.is_empty() {
+                visit(uri, entry.value(), &rope, spans)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shrinks a selection so that it does not start or end on whitespace.
+fn trim_selection(start: u32, end: u32, rope: &Rope) -> Span {
+    let Some(text) = span_text(Span { start, end }, rope) else {
+        return Span { start, end };
+    };
+    let leading = text.len() - text.trim_start().len();
+    let trailing = text.len() - text.trim_end().len();
+    if leading == text.len() {
+        return Span { start, end: start };
+    }
+    Span {
+        start: start + leading as u32,
+        end: end - trailing as u32,
+    }
+}
+
+/// The client command that replaces a range with a snippet, provided by the VS Code extension.
+/// lsp-types has no snippet text edits, so this is how refactorings that introduce a name let
+/// the user pick it right away.
+pub const APPLY_SNIPPET_COMMAND: &str = "l.applySnippet";
+
+/// Stands in for the new name in the edits passed to [`naming_action`].
+pub const NAME_PLACEHOLDER: &str = "\u{0}";
+
+/// An action applying `edits`, which introduce a new name written as [`NAME_PLACEHOLDER`]. When
+/// the client supports snippets, the name becomes a linked placeholder that starts out as `name`;
+/// otherwise the edits are applied with `name` as is.
+pub fn naming_action(
+    ctx: &ActionContext,
+    title: String,
+    kind: CodeActionKind,
+    edits: Vec<(Span, String)>,
+    name: &str,
+) -> Option<CodeAction> {
+    if ctx.snippets {
+        return Some(CodeAction {
+            title,
+            kind: Some(kind),
+            command: Some(snippet_command(ctx.uri, edits, name, ctx.rope)?),
+            ..Default::default()
+        });
+    }
+    let edits = edits
+        .into_iter()
+        .map(|(span, new_text)| {
+            Some(TextEdit::new(
+                span_to_range(span, ctx.rope)?,
+                new_text.replace(NAME_PLACEHOLDER, name),
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(CodeAction {
+        title,
+        kind: Some(kind),
+        edit: Some(single_file_edit(ctx.uri, edits)),
+        ..Default::default()
+    })
+}
+
+/// Merges `edits` into one snippet that replaces the text from the start of the first to the end
+/// of the last.
+fn snippet_command(
+    uri: &Url,
+    mut edits: Vec<(Span, String)>,
+    name: &str,
+    rope: &Rope,
+) -> Option<Command> {
+    let escape = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('$', "\\$")
+            .replace('}', "\\}")
+    };
+    edits.sort_by_key(|(span, _)| span.start);
+    let start = edits.first()?.0.start;
+    let mut cursor = start;
+    let mut snippet = String::new();
+    for (span, new_text) in edits {
+        snippet.push_str(&escape(&span_text(
+            Span {
+                start: cursor,
+                end: span.start,
+            },
+            rope,
+        )?));
+        snippet.push_str(&escape(&new_text));
+        cursor = span.end;
+    }
+    let snippet = snippet
+        .replacen(NAME_PLACEHOLDER, &format!("${{1:{name}}}"), 1)
+        .replace(NAME_PLACEHOLDER, "$1");
+    let range = span_to_range(Span { start, end: cursor }, rope)?;
+    Some(Command {
+        title: "Apply snippet".to_string(),
+        command: APPLY_SNIPPET_COMMAND.to_string(),
+        arguments: Some(vec![json!(uri), json!(range), json!(snippet)]),
+    })
+}
+
+/// A workspace edit that applies `edits` to a single document.
+pub fn single_file_edit(uri: &Url, edits: Vec<TextEdit>) -> WorkspaceEdit {
+    WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))
+}
+
+/// The whitespace between the start of the line containing `offset` and `offset` itself.
+pub fn indentation_at(offset: usize, rope: &Rope) -> String {
+    let Ok(line_idx) = rope.try_byte_to_line(offset) else {
+        return String::new();
+    };
+    let line_start = rope.line_to_byte(line_idx);
+    rope.byte_slice(line_start..offset)
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect()
+}
+
+/// The range to delete to remove the statement or item at `span`, including a trailing `;` and,
+/// when it is alone on its lines, the lines themselves.
+pub fn removal_range(span: Span, rope: &Rope) -> Option<Range> {
+    let span = with_semicolon(span, rope);
+    let mut start = span.start as usize;
+    let mut end = span.end as usize;
+    let len = rope.len_bytes();
+    let line_start = rope.line_to_byte(rope.try_byte_to_line(start).ok()?);
+    let before = rope.byte_slice(line_start..start).to_string();
+    let end_line = rope.try_byte_to_line(end).ok()?;
+    let line_end = if end_line + 1 < rope.len_lines() {
+        rope.line_to_byte(end_line + 1)
+    } else {
+        len
+    };
+    let after = rope.byte_slice(end..line_end).to_string();
+    if before.trim().is_empty() && after.trim().is_empty() {
+        start = line_start;
+        end = line_end;
+    }
+    Some(Range::new(
+        offset_to_position(start, rope)?,
+        offset_to_position(end, rope)?,
+    ))
+}
+
+/// The names of all bindings declared inside `span`.
+pub fn bindings_in(span: Span, semantic_result: &CompileResult, rope: &Rope) -> Vec<String> {
+    semantic_result
+        .semantic
+        .symbol_spans
+        .iter()
+        .filter(|symbol_span| span.start <= symbol_span.start && symbol_span.end <= span.end)
+        .filter_map(|symbol_span| span_text(*symbol_span, rope))
+        .collect()
+}
+
+/// `name`, or `name` with a number appended if it is already `taken`.
+pub fn fresh_name(name: &str, taken: &[String]) -> String {
+    let mut candidate = name.to_string();
+    let mut suffix = 1;
+    while taken.contains(&candidate) {
+        suffix += 1;
+        candidate = format!("{name}{suffix}");
+    }
+    candidate
+}
+
+/// Extends `span` over a `;` that directly follows it.
+pub fn with_semicolon(span: Span, rope: &Rope) -> Span {
+    let end = span.end as usize;
+    let next_char = rope
+        .get_byte_slice(end..rope.len_bytes())
+        .and_then(|rest| rest.chars().next());
+    if next_char == Some(';') {
+        Span {
+            start: span.start,
+            end: span.end + 1,
+        }
+    } else {
+        span
+    }
+}
+
+/// The references in a document that did not resolve and are spelled `name`, which is how uses
+/// of a function declared in another document show up.
+pub fn unresolved_references_named(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    name: &str,
+) -> Vec<Span> {
+    let semantic = &semantic_result.semantic;
+    semantic
+        .reference_spans
+        .iter_enumerated()
+        .filter(|(ref_id, span)| {
+            semantic.references[*ref_id].is_none()
+                && span_text(**span, rope).as_deref() == Some(name)
+        })
+        .map(|(_, span)| *span)
+        .collect()
+}
+
+/// The call whose callee is the name at `callee_span`.
+pub fn call_with_callee(file: &File, callee_span: Span) -> Option<&ExprCall> {
+    ancestors_of_span(file, callee_span)
+        .into_iter()
+        .rev()
+        .find_map(|node| match node {
+            SyntaxNode::Expr(Expr::Call(call_expr))
+                if call_expr.callee.as_deref().map(Expr::span) == Some(callee_span) =>
+            {
+                Some(call_expr)
+            }
+            _ => None,
+        })
+}
+
+/// The range between the parentheses of a call.
+pub fn args_range(call_expr: &ExprCall, rope: &Rope) -> Option<Range> {
+    let callee_end = call_expr.callee.as_deref()?.span().end;
+    let start = after_open_paren(callee_end, rope)?;
+    span_to_range(
+        Span {
+            start,
+            end: call_expr.span.end - 1,
+        },
+        rope,
+    )
+}
+
+/// Whether one of `calls` is inside the arguments of another, which makes their edits overlap.
+pub fn any_nested(calls: &[&ExprCall]) -> bool {
+    calls.iter().any(|outer| {
+        calls.iter().any(|inner| {
+            outer.span != inner.span
+                && outer.span.start <= inner.span.start
+                && inner.span.end <= outer.span.end
+        })
+    })
+}
+
+/// Whether evaluating `expr` calls a function.
+pub fn contains_call(expr: &Expr) -> bool {
+    let mut found = false;
+    walk(SyntaxNode::Expr(expr), &mut |node| {
+        found |= matches!(node, SyntaxNode::Expr(Expr::Call(_)));
+    });
+    found
+}
+
+/// Whether a call in `stmt` finishes before `span` starts, unless `hoisted_too` says it moves
+/// along. Code hoisted from `span` in front of the statement would then run before that call
+/// instead of after it. Calls enclosing `span` run after it, and calls inside it move with it.
+pub fn runs_call_before(stmt: &Stmt, span: Span, hoisted_too: impl Fn(&ExprCall) -> bool) -> bool {
+    let mut found = false;
+    walk(SyntaxNode::Stmt(stmt), &mut |node| {
+        if let SyntaxNode::Expr(Expr::Call(other)) = node {
+            found |= other.span.end <= span.start && !hoisted_too(other);
+        }
+    });
+    found
+}
+
+/// Whether two ranges share more than an end point.
+pub fn ranges_overlap(a: &Range, b: &Range) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// The offset just past the first `(` at or after `offset`.
+pub fn after_open_paren(offset: u32, rope: &Rope) -> Option<u32> {
+    let rest = rope.get_byte_slice(offset as usize..)?;
+    let mut position = offset;
+    for c in rest.chars() {
+        position += c.len_utf8() as u32;
+        if c == '(' {
+            return Some(position);
+        }
+    }
+    None
+}
+
+/// `PointPair` becomes `point_pair`.
+pub fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+pub mod test_support {
+    use std::sync::{OnceLock, RwLock};
+
+    use dashmap::DashMap;
+    use l_lang::compile;
+    use tower_lsp::{ClientSocket, LspService};
+
+    use super::*;
+    use crate::inlay_hints::InlayHintsConfig;
+
+    pub const URI: &str = "file:///main.l";
+
+    /// A compiled document whose selection is marked in the source with one `$0` for a cursor or
+    /// two for a range.
+    pub struct Fixture {
+        pub uri: Url,
+        pub text: String,
+        pub semantic_result: CompileResult,
+        pub rope: Rope,
+        pub selection: Span,
+        pub snippets: bool,
+    }
+
+    impl Fixture {
+        pub fn new(marked: &str) -> Self {
+            let mut offsets = marked.match_indices("$0").map(|(offset, _)| offset);
+            let start = offsets.next().expect("no `$0` marker") as u32;
+            let end = offsets.next().map_or(start, |offset| offset as u32 - 2);
+            let text = marked.replace("$0", "");
+            let semantic_result = compile(&text);
+            assert!(
+                semantic_result.diagnostics.is_empty(),
+                "fixture does not parse: {:?}",
+                semantic_result.diagnostics
+            );
+            Fixture {
+                uri: Url::parse(URI).unwrap(),
+                rope: Rope::from_str(&text),
+                text,
+                semantic_result,
+                selection: Span { start, end },
+                snippets: false,
+            }
+        }
+
+        pub fn ctx(&self) -> ActionContext<'_> {
+            ActionContext {
+                uri: &self.uri,
+                semantic_result: &self.semantic_result,
+                rope: &self.rope,
+                selection: self.selection,
+                snippets: self.snippets,
+            }
+        }
+
+        /// The document after applying the action titled `title`, or `None` if it isn't offered.
+        pub fn apply(&self, actions: &[CodeAction], title: &str) -> Option<String> {
+            let action = actions.iter().find(|action| action.title == title)?;
+            let changes = action.edit.as_ref()?.changes.as_ref()?;
+            Some(apply_edits(&self.text, &changes[&self.uri]))
+        }
+
+        /// The request parameters for the cursor, or the start of the selection.
+        pub fn position_params(&self) -> TextDocumentPositionParams {
+            TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(self.uri.clone()),
+                offset_to_position(self.selection.start as usize, &self.rope).unwrap(),
+            )
+        }
+
+        /// The fixture as [`URI`], followed by `others`, to open together with [`backend`].
+        pub fn with_others<'a>(&'a self, others: &[(&'a str, &'a str)]) -> Vec<(&'a str, &'a str)> {
+            let mut documents = vec![(URI, self.text.as_str())];
+            documents.extend_from_slice(others);
+            documents
+        }
+    }
+
+    /// Applies `edits` to `text`, failing the test if any two of them overlap.
+    pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+        let rope = Rope::from_str(text);
+        let mut spans = edits
+            .iter()
+            .map(|edit| {
+                let start = position_to_offset(edit.range.start, &rope).unwrap();
+                let end = position_to_offset(edit.range.end, &rope).unwrap();
+                (start, end, edit.new_text.as_str())
+            })
+            .collect::<Vec<_>>();
+        spans.sort_by_key(|(start, end, _)| (*start, *end));
+        for pair in spans.windows(2) {
+            assert!(pair[0].1 <= pair[1].0, "overlapping edits: {pair:?}");
+        }
+        let mut result = text.to_string();
+        for (start, end, new_text) in spans.into_iter().rev() {
+            result.replace_range(start..end, new_text);
+        }
+        result
+    }
+
+    /// The text of each of `documents` after applying its part of `changes`.
+    pub fn apply_changes(
+        documents: &[(&str, &str)],
+        changes: &HashMap<Url, Vec<TextEdit>>,
+    ) -> Vec<String> {
+        documents
+            .iter()
+            .map(|(uri, text)| match changes.get(&Url::parse(uri).unwrap()) {
+                Some(edits) => apply_edits(text, edits),
+                None => text.to_string(),
+            })
+            .collect()
+    }
+
+    /// A server with `documents` open, for actions that look across documents.
+    pub fn backend(documents: &[(&str, &str)]) -> (LspService<Backend>, ClientSocket) {
+        let (service, socket) = LspService::build(|client| Backend {
+            client,
+            semanticast_map: DashMap::new(),
+            last_good_map: DashMap::new(),
+            client_capabilities: OnceLock::new(),
+            inlay_hints_config: RwLock::new(InlayHintsConfig::default()),
+            document_map: DashMap::new(),
+        })
+        .finish();
+        for (uri, text) in documents {
+            let backend = service.inner();
+            backend
+                .semanticast_map
+                .insert(uri.to_string(), compile(text));
+            backend
+                .document_map
+                .insert(uri.to_string(), Rope::from_str(text));
+        }
+        (service, socket)
+    }
+}
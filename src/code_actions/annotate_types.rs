@@ -0,0 +1,166 @@
+use l_lang::{Function, Stmt, StmtLet, Type};
+use tower_lsp::lsp_types::*;
+
+use super::{single_file_edit, ActionContext};
+use crate::inlay_hints::params_end;
+use crate::syntax::{ancestors_at, walk, SyntaxNode};
+use crate::types::function_return_type;
+use crate::{offset_to_position, span_to_range};
+
+/// Writes out inferred types: `: Type` on a `let` without one and `-> Type` on a function whose
+/// return type is inferred, for the binding or function under the cursor and for the whole file.
+/// The edits match the spacing the formatter produces, so formatting the result changes nothing.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    let file = ctx.semantic_result.program.file();
+    let mut actions = vec![];
+    for node in ancestors_at(file, ctx.selection.start).into_iter().rev() {
+        let action = match node {
+            SyntaxNode::Stmt(Stmt::Let(let_stmt)) => let_annotation(ctx, let_stmt)
+                .map(|edit| annotate(ctx, "Add type annotation".to_string(), vec![edit])),
+            SyntaxNode::Function(function)
+                if function
+                    .body
+                    .as_ref()
+                    .is_none_or(|body| ctx.selection.start < body.span.start) =>
+            {
+                return_annotation(ctx, function)
+                    .map(|edit| annotate(ctx, "Add return type".to_string(), vec![edit]))
+            }
+            _ => None,
+        };
+        if let Some(action) = action {
+            actions.push(action);
+            break;
+        }
+    }
+
+    let mut edits = vec![];
+    walk(SyntaxNode::File(file), &mut |node| match node {
+        SyntaxNode::Stmt(Stmt::Let(let_stmt)) => edits.extend(let_annotation(ctx, let_stmt)),
+        SyntaxNode::Function(function) => edits.extend(return_annotation(ctx, function)),
+        _ => {}
+    });
+    if !edits.is_empty() {
+        actions.push(annotate(
+            ctx,
+            "Add all missing type annotations in file".to_string(),
+            edits,
+        ));
+    }
+    actions
+}
+
+fn annotate(ctx: &ActionContext, title: String, edits: Vec<TextEdit>) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(single_file_edit(ctx.uri, edits)),
+        ..Default::default()
+    }
+}
+
+/// `: Type` right after the name of a `let` without an annotation.
+fn let_annotation(ctx: &ActionContext, let_stmt: &StmtLet) -> Option<TextEdit> {
+    if let_stmt.ty.is_some() {
+        return None;
+    }
+    let semantic = &ctx.semantic_result.semantic;
+    let name = let_stmt.name.as_ref()?;
+    let symbol_id = semantic.get_symbol_at(name.span.start as usize)?;
+    let ty = semantic.get_symbol_type(symbol_id)?.ty.clone();
+    if ty == Type::Unknown {
+        return None;
+    }
+    let end = span_to_range(name.span, ctx.rope)?.end;
+    Some(TextEdit::new(
+        Range::new(end, end),
+        format!(": {}", ty.format_literal_type(semantic)),
+    ))
+}
+
+/// ` -> Type` right after the parameter list of a function without a return annotation.
+fn return_annotation(ctx: &ActionContext, function: &Function) -> Option<TextEdit> {
+    if function.ret_ty.is_some() {
+        return None;
+    }
+    let ty = function_return_type(function, ctx.semantic_result, ctx.rope)?;
+    let position = offset_to_position(params_end(function, ctx.rope)?, ctx.rope)?;
+    Some(TextEdit::new(
+        Range::new(position, position),
+        format!(
+            " -> {}",
+            ty.format_literal_type(&ctx.semantic_result.semantic)
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::Fixture;
+    use l_lang::{compile, Formatter};
+
+    fn annotate(source: &str, title: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), title)
+    }
+
+    #[test]
+    fn annotates_the_binding_under_the_cursor() {
+        assert_eq!(
+            annotate(
+                "fn f(a: int) -> int {\n    let $0b = a + 1;\n    return b;\n}\n",
+                "Add type annotation"
+            )
+            .unwrap(),
+            "fn f(a: int) -> int {\n    let b: int = a + 1;\n    return b;\n}\n"
+        );
+    }
+
+    #[test]
+    fn infers_the_return_type_from_nested_returns() {
+        assert_eq!(
+            annotate(
+                "fn $0f(a: int) {\n    {\n        return a;\n    }\n}\n",
+                "Add return type"
+            )
+            .unwrap(),
+            "fn f(a: int) -> int {\n    {\n        return a;\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn declines_returns_that_disagree() {
+        let annotate_return =
+            |body: &str| annotate(&format!("fn $0f(a: int) {{\n{body}}}\n"), "Add return type");
+        assert_eq!(
+            annotate_return("    return missing;\n    return a;\n"),
+            None
+        );
+        assert_eq!(
+            annotate_return("    return a;\n    {\n        return \"a\";\n    }\n"),
+            None
+        );
+        assert_eq!(annotate_return("    return;\n    return 1;\n"), None);
+        assert_eq!(annotate_return("    return missing;\n"), None);
+        assert_eq!(
+            annotate_return("    return a;\n    {\n        return 2;\n    }\n").unwrap(),
+            "fn f(a: int) -> int {\n    return a;\n    {\n        return 2;\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn formatting_the_annotated_file_changes_nothing() {
+        let source = "struct Point {\n    x: int,\n}\n\nfn origin() {\n    return Point { x: 0 };\n}\n\nfn $0f(a: int) {\n    let p = origin();\n    let b = p.x + a;\n    return b;\n}\n";
+        let annotated = annotate(source, "Add all missing type annotations in file").unwrap();
+        assert_eq!(
+            annotated,
+            "struct Point {\n    x: int,\n}\n\nfn origin() -> Point {\n    return Point { x: 0 };\n}\n\nfn f(a: int) -> int {\n    let p: Point = origin();\n    let b: int = p.x + a;\n    return b;\n}\n"
+        );
+        let result = compile(&annotated);
+        assert_eq!(
+            Formatter::new(80).format(result.program.file(), &annotated),
+            annotated
+        );
+    }
+}
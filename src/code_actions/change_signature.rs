@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use l_lang::{CompileResult, Span, SymbolKind};
+use ropey::Rope;
+use serde::Deserialize;
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use super::{after_open_paren, any_nested, args_range, call_with_callee, ranges_overlap};
+use crate::inlay_hints::params_end;
+use crate::syntax::find_function_by_name_span;
+use crate::{position_to_offset, span_text, span_to_range, Backend};
+
+pub const CHANGE_SIGNATURE_COMMAND: &str = "l.changeSignature";
+
+/// The argument of the change signature command: the function, named by a position in its
+/// declaration, and its new parameter list.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeSignatureArgs {
+    uri: Url,
+    position: Position,
+    params: Vec<ParamChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParamChange {
+    name: String,
+    ty: String,
+    /// The position of this parameter in the current list, or `None` for a new parameter.
+    #[serde(default)]
+    old_index: Option<usize>,
+    /// The argument to pass for a new parameter. Defaults to the parameter's name.
+    #[serde(default)]
+    default: Option<String>,
+}
+
+impl Backend {
+    /// Rewrites a function's parameter list and every call to it in the indexed documents.
+    pub(crate) fn change_signature(&self, arguments: Vec<Value>) -> Result<WorkspaceEdit, String> {
+        let args = arguments
+            .into_iter()
+            .next()
+            .ok_or("missing arguments")
+            .and_then(|args| {
+                serde_json::from_value::<ChangeSignatureArgs>(args).map_err(|_| "invalid arguments")
+            })?;
+        let key = args.uri.to_string();
+
+        let (name, edits) = {
+            let semantic_result = self
+                .semanticast_map
+                .get(&key)
+                .ok_or("the document is not open")?;
+            let rope = self
+                .document_map
+                .get(&key)
+                .ok_or("the document is not open")?;
+            let semantic = &semantic_result.semantic;
+            let symbol_id = position_to_offset(args.position, &rope)
+                .and_then(|offset| semantic.get_symbol_at(offset))
+                .filter(|symbol_id| semantic.get_symbol_kind(*symbol_id) == SymbolKind::Function)
+                .ok_or("no function at this position")?;
+            let function = find_function_by_name_span(
+                semantic_result.program.file(),
+                semantic.get_symbol_span(symbol_id),
+            )
+            .ok_or("no function at this position")?;
+            let name = function
+                .name
+                .as_ref()
+                .map(|name| name.name.clone())
+                .unwrap_or_default();
+            if let Some(change) = args
+                .params
+                .iter()
+                .find(|change| change.old_index.is_some_and(|i| i >= function.params.len()))
+            {
+                return Err(format!("`{}` has no parameter to move", change.name));
+            }
+
+            let mut edits = vec![];
+            // The parameter list itself.
+            let params_end = params_end(function, &rope).ok_or("unparseable parameter list")?;
+            let params_start = after_open_paren(
+                function
+                    .name
+                    .as_ref()
+                    .map_or(function.span.start, |name| name.span.end),
+                &rope,
+            )
+            .ok_or("unparseable parameter list")?;
+            let new_params = args
+                .params
+                .iter()
+                .map(|change| format!("{}: {}", change.name, change.ty))
+                .collect::<Vec<_>>()
+                .join(", ");
+            edits.push(TextEdit::new(
+                span_to_range(
+                    Span {
+                        start: params_start,
+                        end: params_end as u32 - 1,
+                    },
+                    &rope,
+                )
+                .ok_or("unparseable parameter list")?,
+                new_params,
+            ));
+            // Uses of parameters that are renamed or removed.
+            for (index, param) in function.params.iter().enumerate() {
+                let Some(param_name) = param.name.as_ref() else {
+                    continue;
+                };
+                let Some(param_id) = semantic.get_symbol_at(param_name.span.start as usize) else {
+                    continue;
+                };
+                let references = semantic.get_symbol_references(param_id);
+                match args
+                    .params
+                    .iter()
+                    .find(|change| change.old_index == Some(index))
+                {
+                    Some(change) if change.name != param_name.name => {
+                        for ref_id in references.iter() {
+                            if let Some(range) =
+                                span_to_range(semantic.reference_spans[*ref_id], &rope)
+                            {
+                                edits.push(TextEdit::new(range, change.name.clone()));
+                            }
+                        }
+                    }
+                    Some(_) => {}
+                    None if !references.is_empty() => {
+                        return Err(format!(
+                            "`{}` is still used in the body of `{name}`",
+                            param_name.name
+                        ));
+                    }
+                    None => {}
+                }
+            }
+            let callee_spans = semantic
+                .get_symbol_references(symbol_id)
+                .iter()
+                .map(|ref_id| semantic.reference_spans[*ref_id])
+                .collect::<Vec<_>>();
+            let calls = call_edits(&name, &args.params, &callee_spans, &semantic_result, &rope)?;
+            // A recursive call's arguments may use the parameters being renamed.
+            if calls.iter().any(|call| {
+                edits
+                    .iter()
+                    .any(|edit| ranges_overlap(&call.range, &edit.range))
+            }) {
+                return Err(format!(
+                    "`{name}` passes a renamed parameter to itself; rename it first"
+                ));
+            }
+            edits.extend(calls);
+            (name, edits)
+        };
+
+        let mut changes = HashMap::from([(args.uri.clone(), edits)]);
+        self.try_for_each_use_elsewhere::<String>(
+            &key,
+            &name,
+            |uri, semantic_result, rope, callee_spans| {
+                let edits = call_edits(&name, &args.params, &callee_spans, semantic_result, rope)?;
+                if !edits.is_empty() {
+                    changes.insert(uri, edits);
+                }
+                Ok(())
+            },
+        )?;
+        Ok(WorkspaceEdit::new(changes))
+    }
+}
+
+/// Rewrites the argument list of every call whose callee is one of `callee_spans`. Fails if the
+/// calls are nested in each other, as their edits would overlap.
+fn call_edits(
+    name: &str,
+    params: &[ParamChange],
+    callee_spans: &[Span],
+    semantic_result: &CompileResult,
+    rope: &Rope,
+) -> Result<Vec<TextEdit>, String> {
+    let file = semantic_result.program.file();
+    let calls = callee_spans
+        .iter()
+        .filter_map(|callee_span| call_with_callee(file, *callee_span))
+        .collect::<Vec<_>>();
+    if any_nested(&calls) {
+        return Err(format!(
+            "`{name}` is called in the arguments of a call to itself"
+        ));
+    }
+    Ok(calls
+        .into_iter()
+        .filter_map(|call_expr| {
+            let args = params
+                .iter()
+                .map(|change| match change.old_index {
+                    Some(index) => call_expr
+                        .args
+                        .get(index)
+                        .and_then(|arg| span_text(arg.span(), rope))
+                        .unwrap_or_else(|| change.name.clone()),
+                    None => change
+                        .default
+                        .clone()
+                        .unwrap_or_else(|| change.name.clone()),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(TextEdit::new(args_range(call_expr, rope)?, args))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::code_actions::test_support::{apply_changes, backend, URI};
+
+    /// Applies a signature change to the function declared at `character` on the first line of
+    /// the first document and returns every document afterwards.
+    fn change(
+        documents: &[(&str, &str)],
+        character: u32,
+        params: Value,
+    ) -> Result<Vec<String>, String> {
+        let (service, _socket) = backend(documents);
+        let edit = service.inner().change_signature(vec![json!({
+            "uri": URI,
+            "position": { "line": 0, "character": character },
+            "params": params,
+        })])?;
+        Ok(apply_changes(documents, &edit.changes.unwrap_or_default()))
+    }
+
+    #[test]
+    fn reorders_renames_and_adds_parameters_at_every_call() {
+        let main = "fn sub(a: int, b: int) -> int {\n    return a - b;\n}\n\
+                    fn f() -> int {\n    return sub(1, 2) + sub(3, 4);\n}\n";
+        let other = "fn g() -> int {\n    return sub(5, 6);\n}\n";
+        let params = json!([
+            { "name": "y", "ty": "int", "oldIndex": 1 },
+            { "name": "a", "ty": "int", "oldIndex": 0 },
+            { "name": "c", "ty": "int", "default": "0" },
+        ]);
+        assert_eq!(
+            change(&[(URI, main), ("file:///other.l", other)], 3, params).unwrap(),
+            [
+                "fn sub(y: int, a: int, c: int) -> int {\n    return a - y;\n}\n\
+                 fn f() -> int {\n    return sub(2, 1, 0) + sub(4, 3, 0);\n}\n",
+                "fn g() -> int {\n    return sub(6, 5, 0);\n}\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn reorders_recursive_calls() {
+        let main = "fn r(a: int, b: int) -> int {\n    return r(b, a);\n}\n";
+        let params = json!([
+            { "name": "b", "ty": "int", "oldIndex": 1 },
+            { "name": "a", "ty": "int", "oldIndex": 0 },
+        ]);
+        assert_eq!(
+            change(&[(URI, main)], 3, params).unwrap(),
+            ["fn r(b: int, a: int) -> int {\n    return r(a, b);\n}\n"]
+        );
+    }
+
+    #[test]
+    fn declines_renaming_a_parameter_passed_to_a_recursive_call() {
+        let main = "fn r(a: int) -> int {\n    return r(a - 1);\n}\n";
+        let params = json!([{ "name": "n", "ty": "int", "oldIndex": 0 }]);
+        assert!(change(&[(URI, main)], 3, params).is_err());
+    }
+
+    #[test]
+    fn declines_nested_calls() {
+        let main = "fn f(a: int, b: int) -> int {\n    return a;\n}\n\
+                    fn g() -> int {\n    return f(f(1, 2), 3);\n}\n";
+        let params = json!([
+            { "name": "b", "ty": "int", "oldIndex": 1 },
+            { "name": "a", "ty": "int", "oldIndex": 0 },
+        ]);
+        assert!(change(&[(URI, main)], 3, params).is_err());
+    }
+
+    #[test]
+    fn declines_removing_a_parameter_that_is_used() {
+        let main = "fn f(a: int, b: int) -> int {\n    return a;\n}\n";
+        let params = json!([{ "name": "b", "ty": "int", "oldIndex": 1 }]);
+        assert!(change(&[(URI, main)], 3, params).is_err());
+    }
+}
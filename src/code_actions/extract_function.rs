@@ -0,0 +1,291 @@
+use l_lang::{Expr, Item, Span, Stmt, SymbolId, SymbolKind, Type};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use super::{fresh_name, indentation_at, naming_action, ActionContext, NAME_PLACEHOLDER};
+use crate::span_text;
+use crate::syntax::{ancestors_of_span, walk, SyntaxNode};
+use crate::types::infer_expr_type;
+
+/// "Extract into function" for a run of whole statements or a single expression. The locals the
+/// selection reads become parameters, and a local it declares that is used afterwards becomes
+/// the return value.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    extract(ctx).into_iter().collect()
+}
+
+/// What is being extracted.
+enum Selected<'a> {
+    Stmts(&'a [Stmt]),
+    Expr(&'a Expr),
+}
+
+fn extract(ctx: &ActionContext) -> Option<CodeAction> {
+    let ActionContext {
+        semantic_result,
+        rope,
+        selection,
+        ..
+    } = *ctx;
+    if selection.start == selection.end {
+        return None;
+    }
+    let semantic = &semantic_result.semantic;
+    let file = semantic_result.program.file();
+    let ancestors = ancestors_of_span(file, selection);
+    let function = ancestors.iter().find_map(|node| match node {
+        SyntaxNode::Function(function) => Some(*function),
+        _ => None,
+    })?;
+    let selected =
+        selected_stmts(&ancestors, selection, rope).or_else(|| match ancestors.last() {
+            Some(SyntaxNode::Expr(expr)) if expr.span() == selection => Some(Selected::Expr(expr)),
+            _ => None,
+        })?;
+    let nodes = match selected {
+        Selected::Stmts(stmts) => stmts.iter().map(SyntaxNode::Stmt).collect::<Vec<_>>(),
+        Selected::Expr(expr) => vec![SyntaxNode::Expr(expr)],
+    };
+
+    // Locals declared before the selection and read inside it, in order of first use.
+    let mut inputs: Vec<SymbolId> = vec![];
+    let mut has_return = false;
+    for node in nodes.iter() {
+        walk(*node, &mut |node| match node {
+            SyntaxNode::Stmt(Stmt::Return(_)) => has_return = true,
+            SyntaxNode::Expr(Expr::Name(name_expr)) => {
+                let Some(symbol_id) = semantic
+                    .get_reference_at(name_expr.span.start as usize)
+                    .and_then(|ref_id| semantic.references[ref_id])
+                else {
+                    return;
+                };
+                let declared_at = semantic.get_symbol_span(symbol_id);
+                let is_local = matches!(
+                    semantic.get_symbol_kind(symbol_id),
+                    SymbolKind::Variable | SymbolKind::Parameter
+                );
+                if is_local && declared_at.start < selection.start && !inputs.contains(&symbol_id) {
+                    inputs.push(symbol_id);
+                }
+            }
+            _ => {}
+        });
+    }
+    if has_return {
+        return None;
+    }
+
+    // Locals declared inside the selection and read after it.
+    let outputs = semantic
+        .symbol_spans
+        .iter_enumerated()
+        .filter(|(symbol_id, span)| {
+            selection.start <= span.start
+                && span.end <= selection.end
+                && semantic.get_symbol_kind(*symbol_id) == SymbolKind::Variable
+                && semantic
+                    .get_symbol_references(*symbol_id)
+                    .iter()
+                    .any(|ref_id| semantic.reference_spans[*ref_id].start >= selection.end)
+        })
+        .map(|(symbol_id, _)| symbol_id)
+        .collect::<Vec<_>>();
+    if outputs.len() > 1 {
+        return None;
+    }
+
+    let params = inputs
+        .iter()
+        .map(|symbol_id| {
+            let name = span_text(semantic.get_symbol_span(*symbol_id), rope)?;
+            let ty = semantic.get_symbol_type(*symbol_id)?.ty.clone();
+            if ty == Type::Unknown {
+                return None;
+            }
+            Some((name, ty.format_literal_type(semantic)))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let item_names = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(function) => function.name.as_ref(),
+            Item::Struct(struct_item) => struct_item.name.as_ref(),
+        })
+        .map(|name| name.name.clone())
+        .collect::<Vec<_>>();
+    let name = fresh_name("extracted", &item_names);
+    let args = params
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let call = format!("{NAME_PLACEHOLDER}({args})");
+    let signature = format!(
+        "fn {NAME_PLACEHOLDER}({})",
+        params
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let (new_function, replacement) = match selected {
+        Selected::Expr(expr) => {
+            let ret = infer_expr_type(expr, semantic_result, rope)
+                .filter(|ty| *ty != Type::Unknown)
+                .map(|ty| format!(" -> {}", ty.format_literal_type(semantic)))
+                .unwrap_or_default();
+            (
+                format!(
+                    "{signature}{ret} {{\n    return {};\n}}",
+                    span_text(selection, rope)?
+                ),
+                call,
+            )
+        }
+        Selected::Stmts(_) => {
+            let indent = indentation_at(selection.start as usize, rope);
+            let mut body = span_text(selection, rope)?
+                .lines()
+                .map(|line| format!("    {}", line.strip_prefix(indent.as_str()).unwrap_or(line)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            match outputs.first() {
+                Some(output) => {
+                    let output_name = span_text(semantic.get_symbol_span(*output), rope)?;
+                    let ty = semantic.get_symbol_type(*output)?.ty.clone();
+                    let ret = if ty == Type::Unknown {
+                        String::new()
+                    } else {
+                        format!(" -> {}", ty.format_literal_type(semantic))
+                    };
+                    body.push_str(&format!("\n    return {output_name};"));
+                    let prefix = format!("let {output_name} = ");
+                    (
+                        format!("{signature}{ret} {{\n{body}\n}}"),
+                        format!("{prefix}{call};"),
+                    )
+                }
+                None => (format!("{signature} {{\n{body}\n}}"), format!("{call};")),
+            }
+        }
+    };
+
+    let function_end = Span {
+        start: function.span.end,
+        end: function.span.end,
+    };
+    naming_action(
+        ctx,
+        "Extract into function".to_string(),
+        CodeActionKind::REFACTOR_EXTRACT,
+        vec![
+            (selection, replacement),
+            (function_end, format!("\n\n{new_function}")),
+        ],
+        &name,
+    )
+}
+
+/// The statements of the innermost block that the selection covers exactly, allowing for a
+/// trailing `;` after the last one.
+fn selected_stmts<'a>(
+    ancestors: &[SyntaxNode<'a>],
+    selection: Span,
+    rope: &Rope,
+) -> Option<Selected<'a>> {
+    let block = ancestors.iter().rev().find_map(|node| match node {
+        SyntaxNode::Block(block) => Some(*block),
+        _ => None,
+    })?;
+    let first = block
+        .stmts
+        .iter()
+        .position(|stmt| stmt.span().start == selection.start)?;
+    let last = block.stmts.iter().rposition(|stmt| {
+        let end = stmt.span().end;
+        end <= selection.end
+            && span_text(
+                Span {
+                    start: end,
+                    end: selection.end,
+                },
+                rope,
+            )
+            .is_some_and(|rest| rest.trim().chars().all(|c| c == ';'))
+    })?;
+    if last < first {
+        return None;
+    }
+    Some(Selected::Stmts(&block.stmts[first..=last]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::Fixture;
+
+    fn extract(source: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), "Extract into function")
+    }
+
+    #[test]
+    fn extracts_an_expression() {
+        assert_eq!(
+            extract("fn f(a: int) -> int {\n    return $0a * 2$0 + 1;\n}\n").unwrap(),
+            "fn f(a: int) -> int {\n    return extracted(a) + 1;\n}\n\n\
+             fn extracted(a: int) -> int {\n    return a * 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn extracts_statements_returning_the_local_used_afterwards() {
+        assert_eq!(
+            extract(
+                "fn f(a: int) -> int {\n    $0let b = a + 1;\n    let c = b * 2;$0\n    return c;\n}\n"
+            )
+            .unwrap(),
+            "fn f(a: int) -> int {\n    let c = extracted(a);\n    return c;\n}\n\n\
+             fn extracted(a: int) -> int {\n    let b = a + 1;\n    let c = b * 2;\n    return c;\n}\n"
+        );
+    }
+
+    #[test]
+    fn picks_a_name_no_item_uses() {
+        let result = extract(
+            "struct extracted {\n    x: int,\n}\nfn extracted2() -> int {\n    return 1;\n}\nfn f() -> int {\n    return $01 + 2$0;\n}\n",
+        )
+        .unwrap();
+        assert!(result.contains("return extracted3();"), "{result}");
+        assert!(result.contains("fn extracted3() -> int {"), "{result}");
+    }
+
+    #[test]
+    fn offers_the_name_as_a_snippet_placeholder() {
+        let mut fixture = Fixture::new("fn f(a: int) -> int {\n    return $0a * 2$0;\n}\n");
+        fixture.snippets = true;
+        let actions = actions(&fixture.ctx());
+        let arguments = actions[0]
+            .command
+            .as_ref()
+            .unwrap()
+            .arguments
+            .as_ref()
+            .unwrap();
+        assert_eq!(
+            arguments[2],
+            "${1:extracted}(a);\n\\}\n\nfn $1(a: int) -> int {\n    return a * 2;\n\\}"
+        );
+    }
+
+    #[test]
+    fn declines_selections_containing_return() {
+        assert_eq!(
+            extract("fn f(a: int) -> int {\n    $0return a;$0\n}\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use l_lang::{CompileResult, Expr, Span, SymbolId, Type};
+use tower_lsp::lsp_types::*;
+
+use super::{
+    bindings_in, contains_call, fresh_name, indentation_at, naming_action, runs_call_before,
+    snake_case, ActionContext, NAME_PLACEHOLDER,
+};
+use crate::span_text;
+use crate::syntax::{ancestors_of_span, walk, SyntaxNode};
+use crate::types::infer_expr_type;
+
+/// "Extract into variable" for a selected expression: a `let` before the enclosing statement,
+/// and a second action that also replaces the identical expressions after it in the same block,
+/// as long as their names refer to the same bindings. Expressions containing calls are not
+/// extracted ahead of calls that run before them, and are never merged with their duplicates, as
+/// either would change the calls the program makes.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    extract(ctx).unwrap_or_default()
+}
+
+fn extract(ctx: &ActionContext) -> Option<Vec<CodeAction>> {
+    let ActionContext {
+        semantic_result,
+        rope,
+        selection,
+        ..
+    } = *ctx;
+    if selection.start == selection.end {
+        return None;
+    }
+    let ancestors = ancestors_of_span(semantic_result.program.file(), selection);
+    let SyntaxNode::Expr(expr) = ancestors.last()? else {
+        return None;
+    };
+    if expr.span() != selection {
+        return None;
+    }
+    // A function name on its own is not a value.
+    if let Some(SyntaxNode::Expr(Expr::Call(call_expr))) = ancestors.iter().rev().nth(1) {
+        if call_expr.callee.as_deref().map(Expr::span) == Some(selection) {
+            return None;
+        }
+    }
+    let (block, stmt_index) = ancestors.windows(2).rev().find_map(|pair| match pair {
+        [SyntaxNode::Block(block), SyntaxNode::Stmt(stmt)] => Some((
+            *block,
+            block
+                .stmts
+                .iter()
+                .position(|candidate| candidate.span() == stmt.span())?,
+        )),
+        _ => None,
+    })?;
+    let has_call = contains_call(expr);
+    if has_call && runs_call_before(&block.stmts[stmt_index], selection, |_| false) {
+        return None;
+    }
+    let function_span = ancestors.iter().find_map(|node| match node {
+        SyntaxNode::Function(function) => Some(function.span),
+        _ => None,
+    })?;
+
+    let expr_text = span_text(selection, rope)?;
+    let name = fresh_name(
+        &suggested_name(
+            infer_expr_type(expr, semantic_result, rope),
+            semantic_result,
+        ),
+        &bindings_in(function_span, semantic_result, rope),
+    );
+    let stmt_start = block.stmts[stmt_index].span().start;
+    let declaration = (
+        Span {
+            start: stmt_start,
+            end: stmt_start,
+        },
+        format!(
+            "let {NAME_PLACEHOLDER} = {expr_text};\n{}",
+            indentation_at(stmt_start as usize, rope)
+        ),
+    );
+
+    // Later expressions with the same text that also refer to the same bindings.
+    let names = resolved_names(expr, semantic_result);
+    let mut occurrences: Vec<Span> = vec![];
+    for stmt in block.stmts[stmt_index..].iter() {
+        walk(SyntaxNode::Stmt(stmt), &mut |node| {
+            let SyntaxNode::Expr(candidate) = node else {
+                return;
+            };
+            let span = candidate.span();
+            let nested = occurrences
+                .iter()
+                .any(|outer| outer.start <= span.start && span.end <= outer.end);
+            if !nested
+                && span_text(span, rope).as_deref() == Some(expr_text.as_str())
+                && resolved_names(candidate, semantic_result) == names
+            {
+                occurrences.push(span);
+            }
+        });
+    }
+
+    let build = |title: String, spans: &[Span]| -> Option<CodeAction> {
+        let mut edits = vec![declaration.clone()];
+        for span in spans {
+            edits.push((*span, NAME_PLACEHOLDER.to_string()));
+        }
+        naming_action(ctx, title, CodeActionKind::REFACTOR_EXTRACT, edits, &name)
+    };
+    let mut actions = vec![build("Extract into variable".to_string(), &[selection])?];
+    if occurrences.len() > 1 && !has_call {
+        actions.push(build(
+            format!(
+                "Extract into variable, replacing all {} occurrences",
+                occurrences.len()
+            ),
+            &occurrences,
+        )?);
+    }
+    Some(actions)
+}
+
+/// What each name in `expr` refers to, in order.
+fn resolved_names(expr: &Expr, semantic_result: &CompileResult) -> Vec<Option<SymbolId>> {
+    let semantic = &semantic_result.semantic;
+    let mut names = vec![];
+    walk(SyntaxNode::Expr(expr), &mut |node| {
+        if let SyntaxNode::Expr(Expr::Name(name_expr)) = node {
+            names.push(
+                semantic
+                    .get_reference_at(name_expr.span.start as usize)
+                    .and_then(|ref_id| semantic.references[ref_id]),
+            );
+        }
+    });
+    names
+}
+
+/// A variable name derived from a type: `point_pair` for a `PointPair`, `int_value` for an `int`.
+fn suggested_name(ty: Option<Type>, semantic_result: &CompileResult) -> String {
+    let Some(ty) = ty.filter(|ty| *ty != Type::Unknown) else {
+        return "value".to_string();
+    };
+    let type_name = ty.format_literal_type(&semantic_result.semantic);
+    match ty {
+        Type::Struct(_) => snake_case(&type_name),
+        _ => format!("{type_name}_value"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::Fixture;
+
+    const ALL: &str = "Extract into variable, replacing all 2 occurrences";
+
+    fn extract(source: &str, title: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), title)
+    }
+
+    #[test]
+    fn extracts_before_the_enclosing_statement() {
+        assert_eq!(
+            extract(
+                "fn f(a: int) -> int {\n    return $0a + 1$0;\n}\n",
+                "Extract into variable"
+            )
+            .unwrap(),
+            "fn f(a: int) -> int {\n    let int_value = a + 1;\n    return int_value;\n}\n"
+        );
+    }
+
+    #[test]
+    fn replaces_identical_occurrences() {
+        assert_eq!(
+            extract(
+                "fn f(a: int) -> int {\n    let b = $0a * 2$0;\n    return a * 2 + b;\n}\n",
+                ALL
+            )
+            .unwrap(),
+            "fn f(a: int) -> int {\n    let int_value = a * 2;\n    let b = int_value;\n    \
+             return int_value + b;\n}\n"
+        );
+    }
+
+    #[test]
+    fn skips_occurrences_whose_names_are_shadowed() {
+        let source = "fn f(a: int) -> int {\n    let b = $0a * 2$0;\n    let a = 3;\n    \
+                      return a * 2 + b;\n}\n";
+        assert_eq!(extract(source, ALL), None);
+    }
+
+    #[test]
+    fn keeps_duplicate_calls() {
+        let source = "fn g(a: int) -> int {\n    return a;\n}\n\nfn f(a: int) -> int {\n    \
+                      return $0g(a)$0 + g(a);\n}\n";
+        assert_eq!(extract(source, ALL), None);
+        assert_eq!(
+            extract(source, "Extract into variable").unwrap(),
+            "fn g(a: int) -> int {\n    return a;\n}\n\nfn f(a: int) -> int {\n    \
+             let int_value = g(a);\n    return int_value + g(a);\n}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_calls_in_order() {
+        let g = "fn g(a: int) -> int {\n    return a;\n}\n\nfn h(a: int) -> int {\n    return a;\n}\n\n";
+        assert_eq!(
+            extract(
+                &format!("{g}fn f() -> int {{\n    return g(1) + h($0g(2)$0);\n}}\n"),
+                "Extract into variable"
+            ),
+            None
+        );
+        assert_eq!(
+            extract(
+                &format!("{g}fn f() -> int {{\n    return g(1) + h($0g(2) + 1$0) + 1;\n}}\n"),
+                "Extract into variable"
+            ),
+            None
+        );
+        assert_eq!(
+            extract(
+                &format!("{g}fn f() -> int {{\n    return g(1) + h($01 + 2$0);\n}}\n"),
+                "Extract into variable"
+            )
+            .unwrap(),
+            format!("{g}fn f() -> int {{\n    let int_value = 1 + 2;\n    return g(1) + h(int_value);\n}}\n")
+        );
+    }
+
+    #[test]
+    fn offers_the_name_as_a_snippet_placeholder() {
+        let mut fixture = Fixture::new(
+            "fn f(a: int) -> int {\n    let b = $0a * 2$0;\n    return a * 2 + b;\n}\n",
+        );
+        fixture.snippets = true;
+        let actions = actions(&fixture.ctx());
+        let action = actions.iter().find(|action| action.title == ALL).unwrap();
+        assert!(action.edit.is_none());
+        let arguments = action.command.as_ref().unwrap().arguments.as_ref().unwrap();
+        assert_eq!(
+            arguments[2],
+            "let ${1:int_value} = a * 2;\n    let b = $1;\n    return $1"
+        );
+    }
+}
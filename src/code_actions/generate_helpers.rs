@@ -0,0 +1,287 @@
+use l_lang::{CompileResult, Item, Struct, SymbolId, Type};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use super::{single_file_edit, snake_case, ActionContext};
+use crate::syntax::{ancestors_at, SyntaxNode};
+use crate::{offset_to_position, span_text};
+
+/// On a struct declaration, actions that add a constructor and a to-string function for it,
+/// unless the file already has one. There is no equality helper, as the language has no
+/// comparison operators to write one with.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    generate(ctx).unwrap_or_default()
+}
+
+fn generate(ctx: &ActionContext) -> Option<Vec<CodeAction>> {
+    let ActionContext {
+        uri,
+        semantic_result,
+        rope,
+        selection,
+        ..
+    } = *ctx;
+    let struct_decl = ancestors_at(semantic_result.program.file(), selection.start)
+        .into_iter()
+        .find_map(|node| match node {
+            SyntaxNode::Struct(struct_decl) => Some(struct_decl),
+            _ => None,
+        })?;
+    let name = &struct_decl.name.as_ref()?.name;
+    let struct_id = semantic_result
+        .semantic
+        .get_symbol_at(struct_decl.name.as_ref()?.span.start as usize)?;
+    let helpers = Helpers {
+        semantic_result,
+        rope,
+        existing: function_names(semantic_result),
+    };
+    let insert_at = offset_to_position(struct_decl.span.end as usize, rope)?;
+    let action = |title: String, functions: Vec<String>| CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(single_file_edit(
+            uri,
+            vec![TextEdit::new(
+                Range::new(insert_at, insert_at),
+                functions
+                    .iter()
+                    .map(|function| format!("\n\n{function}"))
+                    .collect(),
+            )],
+        )),
+        ..Default::default()
+    };
+
+    let snake = snake_case(name);
+    let mut actions = vec![];
+    if !helpers.exists(&format!("new_{snake}")) {
+        actions.push(action(
+            format!("Generate `new_{snake}`"),
+            vec![constructor(struct_decl)?],
+        ));
+    }
+    let mut generated = Generated::default();
+    if !helpers.exists(&format!("{snake}_to_string"))
+        && helpers.stringify(struct_id, &mut generated).is_some()
+    {
+        actions.push(action(
+            format!("Generate `{snake}_to_string`"),
+            generated.functions,
+        ));
+    }
+    Some(actions)
+}
+
+/// `fn new_point(x: int, y: int) -> Point`, taking the fields as written in the declaration.
+fn constructor(struct_decl: &Struct) -> Option<String> {
+    let name = &struct_decl.name.as_ref()?.name;
+    let fields = struct_decl
+        .fields
+        .iter()
+        .map(|field| {
+            Some((
+                field.name.as_ref()?.name.as_str(),
+                field.ty.as_ref()?.name.as_str(),
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let params = fields
+        .iter()
+        .map(|(field, ty)| format!("{field}: {ty}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let inits = fields
+        .iter()
+        .map(|(field, _)| format!("{field}: {field}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "fn new_{}({params}) -> {name} {{\n    return {name} {{ {inits} }};\n}}",
+        snake_case(name)
+    ))
+}
+
+fn function_names(semantic_result: &CompileResult) -> Vec<String> {
+    semantic_result
+        .program
+        .file()
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(function) => Some(function.name.as_ref()?.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The helpers generated by one action, in the order they are inserted.
+#[derive(Default)]
+struct Generated {
+    names: Vec<String>,
+    functions: Vec<String>,
+}
+
+impl Generated {
+    /// Records that `name` is being generated, returning `false` if it already was.
+    fn start(&mut self, name: &str) -> bool {
+        if self.names.iter().any(|generated| generated == name) {
+            return false;
+        }
+        self.names.push(name.to_string());
+        true
+    }
+}
+
+/// Generates the to-string helper for a struct and, for its struct-typed fields, the helper for
+/// the nested struct unless the file already has it.
+struct Helpers<'a> {
+    semantic_result: &'a CompileResult,
+    rope: &'a Rope,
+    existing: Vec<String>,
+}
+
+impl Helpers<'_> {
+    fn exists(&self, name: &str) -> bool {
+        self.existing.iter().any(|existing| existing == name)
+    }
+
+    fn struct_name(&self, struct_id: SymbolId) -> Option<String> {
+        span_text(
+            self.semantic_result.semantic.get_symbol_span(struct_id),
+            self.rope,
+        )
+    }
+
+    /// `fn point_to_string(value: Point) -> string`, rendering the struct like a literal.
+    /// `int` and `bool` fields go through `int_to_string` and `bool_to_string`, so this fails
+    /// if the struct has such a field and the file doesn't define the matching function.
+    fn stringify(&self, struct_id: SymbolId, generated: &mut Generated) -> Option<String> {
+        let name = self.struct_name(struct_id)?;
+        let function_name = format!("{}_to_string", snake_case(&name));
+        if !generated.start(&function_name) {
+            return Some(function_name);
+        }
+        let fields = self
+            .semantic_result
+            .semantic
+            .structs
+            .get(&struct_id)?
+            .fields
+            .iter()
+            .map(|field| {
+                let value = format!("value.{}", field.name);
+                let rendered = match &field.ty {
+                    Type::Struct(nested_id) => {
+                        let nested = self.nested_helper(
+                            *nested_id,
+                            "_to_string",
+                            generated,
+                            Self::stringify,
+                        )?;
+                        format!("{nested}({value})")
+                    }
+                    Type::Int if self.exists("int_to_string") => format!("int_to_string({value})"),
+                    Type::Bool if self.exists("bool_to_string") => {
+                        format!("bool_to_string({value})")
+                    }
+                    Type::String => value,
+                    _ => return None,
+                };
+                Some((field.name.as_str(), rendered))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let body = if fields.is_empty() {
+            format!("\"{name} {{}}\"")
+        } else {
+            let mut parts = vec![];
+            for (index, (field, rendered)) in fields.iter().enumerate() {
+                let separator = if index == 0 {
+                    format!("{name} {{ ")
+                } else {
+                    ", ".to_string()
+                };
+                parts.push(format!("\"{separator}{field}: \""));
+                parts.push(rendered.clone());
+            }
+            parts.push("\" }\"".to_string());
+            parts.join(" + ")
+        };
+        let function =
+            format!("fn {function_name}(value: {name}) -> string {{\n    return {body};\n}}");
+        generated.functions.push(function);
+        Some(function_name)
+    }
+
+    /// The name of the helper for a nested struct, generating it if the file lacks one.
+    fn nested_helper(
+        &self,
+        struct_id: SymbolId,
+        suffix: &str,
+        generated: &mut Generated,
+        generate: fn(&Self, SymbolId, &mut Generated) -> Option<String>,
+    ) -> Option<String> {
+        let function_name = format!("{}{suffix}", snake_case(&self.struct_name(struct_id)?));
+        if self.exists(&function_name) {
+            return Some(function_name);
+        }
+        generate(self, struct_id, generated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::Fixture;
+
+    const POINT: &str = "struct $0Point {\n    x: int,\n    label: string,\n}\n";
+
+    fn generate(source: &str, title: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), title)
+    }
+
+    #[test]
+    fn generates_a_constructor() {
+        assert_eq!(
+            generate(POINT, "Generate `new_point`").unwrap(),
+            "struct Point {\n    x: int,\n    label: string,\n}\n\n\
+             fn new_point(x: int, label: string) -> Point {\n    \
+             return Point { x: x, label: label };\n}\n"
+        );
+    }
+
+    #[test]
+    fn generates_to_string_with_nested_helpers() {
+        let source = format!(
+            "{POINT}struct $0Line {{\n    from: Point,\n}}\n\
+             fn int_to_string(n: int) -> string {{\n    return \"\";\n}}\n"
+        )
+        .replacen("$0Point", "Point", 1);
+        let result = generate(&source, "Generate `line_to_string`").unwrap();
+        assert!(result.contains(
+            "fn point_to_string(value: Point) -> string {\n    return \"Point { x: \" + \
+             int_to_string(value.x) + \", label: \" + value.label + \" }\";\n}"
+        ));
+        assert!(result.contains(
+            "fn line_to_string(value: Line) -> string {\n    return \"Line { from: \" + \
+             point_to_string(value.from) + \" }\";\n}"
+        ));
+        // Every generated function has to parse.
+        assert!(l_lang::compile(&result).diagnostics.is_empty(), "{result}");
+    }
+
+    #[test]
+    fn offers_no_to_string_without_helpers_for_int_fields() {
+        assert_eq!(generate(POINT, "Generate `point_to_string`"), None);
+        assert!(generate(POINT, "Generate `new_point`").is_some());
+    }
+
+    #[test]
+    fn offers_no_equality_helper() {
+        let fixture = Fixture::new(POINT);
+        assert!(actions(&fixture.ctx())
+            .iter()
+            .all(|action| !action.title.contains("_eq")));
+    }
+}
@@ -0,0 +1,264 @@
+use l_lang::{CompileResult, Expr, ExprCall, ExprStruct, Span, Type};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use super::{fresh_name, single_file_edit, ActionContext};
+use crate::syntax::{ancestors_of_span, SyntaxNode};
+use crate::types::{expected_type_at, infer_expr_type};
+use crate::{offset_to_position, span_text};
+
+/// How deep a generated default value nests struct literals.
+const MAX_DEFAULT_DEPTH: u8 = 4;
+
+/// Quick fixes for unresolved names at the selection: a struct declaration generated from a
+/// struct literal, or a function stub generated from a call.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    let semantic = &ctx.semantic_result.semantic;
+    let file = ctx.semantic_result.program.file();
+    let mut actions: Vec<CodeAction> = vec![];
+    for error in semantic.errors.iter() {
+        if error.span.end < ctx.selection.start || ctx.selection.end < error.span.start {
+            continue;
+        }
+        let unresolved = semantic
+            .get_reference_at(error.span.start as usize)
+            .is_some_and(|ref_id| semantic.references[ref_id].is_none());
+        if !unresolved {
+            continue;
+        }
+        let ancestors = ancestors_of_span(file, error.span);
+        let action = ancestors.iter().rev().find_map(|node| match node {
+            SyntaxNode::Expr(Expr::Struct(struct_expr))
+                if struct_expr.name.as_ref().map(|name| name.span) == Some(error.span) =>
+            {
+                Some(generate_struct(ctx, struct_expr, &ancestors))
+            }
+            SyntaxNode::Expr(Expr::Call(call_expr))
+                if call_expr.callee.as_deref().map(Expr::span) == Some(error.span) =>
+            {
+                Some(generate_function(ctx, call_expr, &ancestors))
+            }
+            _ => None,
+        });
+        if let Some(action) = action.flatten() {
+            if !actions
+                .iter()
+                .any(|existing| existing.title == action.title)
+            {
+                actions.push(action);
+            }
+        }
+    }
+    actions
+}
+
+fn generate_struct(
+    ctx: &ActionContext,
+    struct_expr: &ExprStruct,
+    ancestors: &[SyntaxNode],
+) -> Option<CodeAction> {
+    let semantic_result = ctx.semantic_result;
+    let name = &struct_expr.name.as_ref()?.name;
+    let fields = struct_expr
+        .fields
+        .iter()
+        .map(|field| {
+            let ty = known_type(field.value.as_deref(), semantic_result, ctx.rope)?;
+            Some(format!(
+                "    {}: {},\n",
+                field.name.as_ref()?.name,
+                ty.format_literal_type(&semantic_result.semantic)
+            ))
+        })
+        .collect::<Option<String>>()?;
+    let item_start = enclosing_item(ancestors)?.start;
+    let insert_at = offset_to_position(item_start as usize, ctx.rope)?;
+    Some(quick_fix(
+        ctx,
+        format!("Generate struct `{name}`"),
+        TextEdit::new(
+            Range::new(insert_at, insert_at),
+            format!("struct {name} {{\n{fields}}}\n\n"),
+        ),
+    ))
+}
+
+fn generate_function(
+    ctx: &ActionContext,
+    call_expr: &ExprCall,
+    ancestors: &[SyntaxNode],
+) -> Option<CodeAction> {
+    let ActionContext {
+        semantic_result,
+        rope,
+        ..
+    } = *ctx;
+    let semantic = &semantic_result.semantic;
+    let Some(Expr::Name(callee)) = call_expr.callee.as_deref() else {
+        return None;
+    };
+    let mut names: Vec<String> = vec![];
+    let mut params = vec![];
+    for (index, arg) in call_expr.args.iter().enumerate() {
+        let base = match arg {
+            Expr::Name(name_expr) => name_expr.name.clone(),
+            Expr::Field(field_expr) => field_expr.field.as_ref()?.name.clone(),
+            _ => format!("arg{}", index + 1),
+        };
+        let name = fresh_name(&base, &names);
+        let ty = known_type(Some(arg), semantic_result, rope)?;
+        params.push(format!("{name}: {}", ty.format_literal_type(semantic)));
+        names.push(name);
+    }
+    let signature = format!("fn {}({})", callee.name, params.join(", "));
+    // Without a value to return, the stub has no return type.
+    let stub = expected_type_at(semantic_result, rope, call_expr.span.start as usize)
+        .filter(|ty| *ty != Type::Unknown)
+        .and_then(|ty| {
+            Some(format!(
+                "{signature} -> {} {{\n    return {};\n}}",
+                ty.format_literal_type(semantic),
+                default_value(&ty, semantic_result, rope, MAX_DEFAULT_DEPTH)?
+            ))
+        })
+        .unwrap_or_else(|| format!("{signature} {{\n}}"));
+    let item_end = enclosing_item(ancestors)?.end;
+    let insert_at = offset_to_position(item_end as usize, rope)?;
+    Some(quick_fix(
+        ctx,
+        format!("Generate function `{}`", callee.name),
+        TextEdit::new(Range::new(insert_at, insert_at), format!("\n\n{stub}")),
+    ))
+}
+
+fn quick_fix(ctx: &ActionContext, title: String, edit: TextEdit) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(single_file_edit(ctx.uri, vec![edit])),
+        is_preferred: Some(true),
+        ..Default::default()
+    }
+}
+
+/// The span of the top-level item the node belongs to.
+fn enclosing_item(ancestors: &[SyntaxNode]) -> Option<Span> {
+    ancestors.iter().find_map(|node| match node {
+        SyntaxNode::Function(function) => Some(function.span),
+        SyntaxNode::Struct(struct_decl) => Some(struct_decl.span),
+        _ => None,
+    })
+}
+
+fn known_type(expr: Option<&Expr>, semantic_result: &CompileResult, rope: &Rope) -> Option<Type> {
+    infer_expr_type(expr?, semantic_result, rope).filter(|ty| *ty != Type::Unknown)
+}
+
+/// A placeholder value of the given type, for the body of a generated function.
+fn default_value(
+    ty: &Type,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    depth: u8,
+) -> Option<String> {
+    match ty {
+        Type::Int => Some("0".to_string()),
+        Type::Bool => Some("false".to_string()),
+        Type::String => Some("\"\"".to_string()),
+        Type::Unknown => None,
+        Type::Struct(struct_id) => {
+            if depth == 0 {
+                return None;
+            }
+            let semantic = &semantic_result.semantic;
+            let name = span_text(semantic.get_symbol_span(*struct_id), rope)?;
+            let fields = semantic
+                .structs
+                .get(struct_id)?
+                .fields
+                .iter()
+                .map(|field| {
+                    Some(format!(
+                        "{}: {}",
+                        field.name,
+                        default_value(&field.ty, semantic_result, rope, depth - 1)?
+                    ))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            if fields.is_empty() {
+                return Some(format!("{name} {{}}"));
+            }
+            Some(format!("{name} {{ {} }}", fields.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::Fixture;
+
+    fn generate(source: &str, title: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), title)
+    }
+
+    #[test]
+    fn generates_a_struct_above_the_current_item() {
+        assert_eq!(
+            generate(
+                "fn f(a: int) {\n    let v = $0Vector { x: a, y: true };\n}\n",
+                "Generate struct `Vector`"
+            )
+            .unwrap(),
+            "struct Vector {\n    x: int,\n    y: bool,\n}\n\nfn f(a: int) {\n    let v = Vector { x: a, y: true };\n}\n"
+        );
+    }
+
+    #[test]
+    fn generates_a_function_returning_the_expected_type() {
+        assert_eq!(
+            generate(
+                "struct Point {\n    x: int,\n}\n\nfn f(p: Point) {\n    let q: Point = $0scale(p, p.x, 3);\n}\n",
+                "Generate function `scale`"
+            )
+            .unwrap(),
+            "struct Point {\n    x: int,\n}\n\nfn f(p: Point) {\n    let q: Point = scale(p, p.x, 3);\n}\n\nfn scale(p: Point, x: int, arg3: int) -> Point {\n    return Point { x: 0 };\n}\n"
+        );
+    }
+
+    #[test]
+    fn generates_a_function_without_a_return_type_when_the_value_is_unused() {
+        assert_eq!(
+            generate(
+                "fn f(a: int) {\n    $0log(a, a);\n}\n",
+                "Generate function `log`"
+            )
+            .unwrap(),
+            "fn f(a: int) {\n    log(a, a);\n}\n\nfn log(a: int, a2: int) {\n}\n"
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_return_type_without_a_default_value() {
+        assert_eq!(
+            generate(
+                "struct Node {\n    next: Node,\n}\n\nfn f() {\n    let n: Node = $0make();\n}\n",
+                "Generate function `make`"
+            )
+            .unwrap(),
+            "struct Node {\n    next: Node,\n}\n\nfn f() {\n    let n: Node = make();\n}\n\nfn make() {\n}\n"
+        );
+    }
+
+    #[test]
+    fn declines_calls_with_arguments_of_unknown_type() {
+        assert_eq!(
+            generate(
+                "fn f() {\n    $0log(missing);\n}\n",
+                "Generate function `log`"
+            ),
+            None
+        );
+    }
+}
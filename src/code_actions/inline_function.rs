@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+
+use l_lang::{CompileResult, Expr, ExprCall, Function, Span, Stmt, SymbolKind};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use super::{
+    any_nested, bindings_in, call_with_callee, contains_call, fresh_name, indentation_at,
+    removal_range, runs_call_before, single_file_edit, with_semicolon, ActionContext,
+};
+use crate::syntax::{
+    ancestors_at, ancestors_of_span, find_function_by_name_span, walk, SyntaxNode,
+};
+use crate::types::resolve_callee;
+use crate::{offset_to_position, span_text, span_to_range, Backend};
+
+/// A piece of the body of a function being inlined.
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    /// A use of the parameter at this index.
+    Param(usize),
+    /// The declaration or a use of the body local at this index.
+    Local(usize),
+}
+
+/// The body of a function prepared for inlining. It owns its text so that it can be applied to
+/// documents other than the one it came from.
+#[derive(Debug)]
+struct InlineTemplate {
+    name: String,
+    params: Vec<String>,
+    locals: Vec<String>,
+    /// The statements before the final `return`.
+    stmts: Vec<Segment>,
+    /// The value of the final `return`, if there is one.
+    value: Option<Vec<Segment>>,
+    value_is_binary: bool,
+    value_has_call: bool,
+    /// The indentation of the body's statements.
+    indent: String,
+}
+
+/// "Inline `f`" on the name of a call to a function in the same document.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    inline_at_call_site(ctx).into_iter().collect()
+}
+
+fn inline_at_call_site(ctx: &ActionContext) -> Option<CodeAction> {
+    let ActionContext {
+        uri,
+        semantic_result,
+        rope,
+        selection,
+        ..
+    } = *ctx;
+    let call_expr = ancestors_at(semantic_result.program.file(), selection.start)
+        .into_iter()
+        .rev()
+        .find_map(|node| match node {
+            SyntaxNode::Expr(Expr::Call(call_expr)) => Some(call_expr),
+            _ => None,
+        })?;
+    let callee = call_expr.callee.as_deref()?;
+    if selection.start < callee.span().start || callee.span().end < selection.end {
+        return None;
+    }
+    let function = resolve_callee(callee, semantic_result)?;
+    let template = InlineTemplate::new(function, semantic_result, rope)?;
+    let edits = inline_call(
+        &template,
+        call_expr,
+        &[],
+        &mut HashMap::new(),
+        semantic_result,
+        rope,
+    )?;
+    Some(CodeAction {
+        title: format!("Inline `{}`", template.name),
+        kind: Some(CodeActionKind::REFACTOR_INLINE),
+        edit: Some(single_file_edit(uri, edits)),
+        ..Default::default()
+    })
+}
+
+impl Backend {
+    /// "Inline all calls and delete" on the name of a function declaration. Calls in other
+    /// indexed documents are the unresolved references with the function's name.
+    pub(super) fn inline_all_action(&self, uri: &Url, offset: u32) -> Option<CodeAction> {
+        let key = uri.to_string();
+        let (template, edits) = {
+            let semantic_result = self.semanticast_map.get(&key)?;
+            let rope = self.document_map.get(&key)?;
+            let semantic = &semantic_result.semantic;
+            let interval = semantic
+                .span_to_symbol
+                .find(offset as usize, offset as usize + 1)
+                .next()?;
+            let symbol_id = interval.val;
+            if semantic.get_symbol_kind(symbol_id) != SymbolKind::Function {
+                return None;
+            }
+            let function = find_function_by_name_span(
+                semantic_result.program.file(),
+                semantic.get_symbol_span(symbol_id),
+            )?;
+            let template = InlineTemplate::new(function, &semantic_result, &rope)?;
+            let callee_spans = semantic
+                .get_symbol_references(symbol_id)
+                .iter()
+                .map(|ref_id| semantic.reference_spans[*ref_id])
+                .collect::<Vec<_>>();
+            let mut edits = inline_calls(&template, &callee_spans, &semantic_result, &rope)?;
+            edits.push(TextEdit::new(
+                removal_range(function.span, &rope)?,
+                String::new(),
+            ));
+            (template, edits)
+        };
+
+        let mut changes = HashMap::from([(uri.clone(), edits)]);
+        self.try_for_each_use_elsewhere::<()>(
+            &key,
+            &template.name,
+            |other_uri, semantic_result, rope, callee_spans| {
+                let edits =
+                    inline_calls(&template, &callee_spans, semantic_result, rope).ok_or(())?;
+                changes.insert(other_uri, edits);
+                Ok(())
+            },
+        )
+        .ok()?;
+        Some(CodeAction {
+            title: format!("Inline all calls to `{}` and delete it", template.name),
+            kind: Some(CodeActionKind::REFACTOR_INLINE),
+            edit: Some(WorkspaceEdit::new(changes)),
+            ..Default::default()
+        })
+    }
+}
+
+impl InlineTemplate {
+    /// Fails for functions that call themselves or `return` anywhere but at the end.
+    fn new(function: &Function, semantic_result: &CompileResult, rope: &Rope) -> Option<Self> {
+        let semantic = &semantic_result.semantic;
+        let name = function.name.as_ref()?;
+        let body = function.body.as_ref()?;
+        let (stmts, value) = match body.stmts.split_last() {
+            Some((Stmt::Return(return_stmt), rest)) => (rest, return_stmt.value.as_deref()),
+            _ => (&body.stmts[..], None),
+        };
+
+        let mut inlinable = true;
+        let nodes = stmts
+            .iter()
+            .map(SyntaxNode::Stmt)
+            .chain(value.map(SyntaxNode::Expr));
+        for node in nodes {
+            walk(node, &mut |node| match node {
+                SyntaxNode::Stmt(Stmt::Return(_)) => inlinable = false,
+                SyntaxNode::Expr(Expr::Call(call_expr)) => {
+                    let recursive = call_expr
+                        .callee
+                        .as_deref()
+                        .and_then(|callee| resolve_callee(callee, semantic_result))
+                        .is_some_and(|callee| callee.span == function.span);
+                    inlinable &= !recursive;
+                }
+                _ => {}
+            });
+        }
+        if !inlinable {
+            return None;
+        }
+
+        let mut occurrences: Vec<(Span, Segment)> = vec![];
+        let mut params = vec![];
+        for (index, param) in function.params.iter().enumerate() {
+            let param_name = param.name.as_ref()?;
+            let symbol_id = semantic.get_symbol_at(param_name.span.start as usize)?;
+            params.push(param_name.name.clone());
+            for ref_id in semantic.get_symbol_references(symbol_id).iter() {
+                occurrences.push((semantic.reference_spans[*ref_id], Segment::Param(index)));
+            }
+        }
+        let mut locals = vec![];
+        for (symbol_id, span) in semantic.symbol_spans.iter_enumerated() {
+            if semantic.get_symbol_kind(symbol_id) != SymbolKind::Variable
+                || span.start < body.span.start
+                || body.span.end < span.end
+            {
+                continue;
+            }
+            let index = locals.len();
+            locals.push(span_text(*span, rope)?);
+            occurrences.push((*span, Segment::Local(index)));
+            for ref_id in semantic.get_symbol_references(symbol_id).iter() {
+                occurrences.push((semantic.reference_spans[*ref_id], Segment::Local(index)));
+            }
+        }
+        occurrences.sort_by_key(|(span, _)| span.start);
+
+        let stmts_segments = match (stmts.first(), stmts.last()) {
+            (Some(first), Some(last)) => segments(
+                with_semicolon(
+                    Span {
+                        start: first.span().start,
+                        end: last.span().end,
+                    },
+                    rope,
+                ),
+                &occurrences,
+                rope,
+            )?,
+            _ => vec![],
+        };
+        let value_segments = match value {
+            Some(value) => Some(segments(value.span(), &occurrences, rope)?),
+            None => None,
+        };
+        Some(Self {
+            name: name.name.clone(),
+            params,
+            locals,
+            stmts: stmts_segments,
+            value: value_segments,
+            value_is_binary: matches!(value, Some(Expr::Binary(_))),
+            value_has_call: value.is_some_and(contains_call),
+            indent: stmts
+                .first()
+                .map(|stmt| indentation_at(stmt.span().start as usize, rope))
+                .unwrap_or_default(),
+        })
+    }
+
+    fn render(&self, segments: &[Segment], params: &[String], locals: &[String]) -> String {
+        segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.as_str(),
+                Segment::Param(index) => params[*index].as_str(),
+                Segment::Local(index) => locals[*index].as_str(),
+            })
+            .collect()
+    }
+}
+
+/// Splits the text of `span` at the given occurrences.
+fn segments(span: Span, occurrences: &[(Span, Segment)], rope: &Rope) -> Option<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut cursor = span.start;
+    for (occurrence, segment) in occurrences {
+        if occurrence.start < span.start || span.end < occurrence.end {
+            continue;
+        }
+        segments.push(Segment::Text(span_text(
+            Span {
+                start: cursor,
+                end: occurrence.start,
+            },
+            rope,
+        )?));
+        segments.push(segment.clone());
+        cursor = occurrence.end;
+    }
+    segments.push(Segment::Text(span_text(
+        Span {
+            start: cursor,
+            end: span.end,
+        },
+        rope,
+    )?));
+    Some(segments)
+}
+
+/// Inlines every call whose callee is one of `callee_spans`. Fails if one of them is not called
+/// or the calls are nested in each other.
+fn inline_calls(
+    template: &InlineTemplate,
+    callee_spans: &[Span],
+    semantic_result: &CompileResult,
+    rope: &Rope,
+) -> Option<Vec<TextEdit>> {
+    let file = semantic_result.program.file();
+    let calls = callee_spans
+        .iter()
+        .map(|callee_span| call_with_callee(file, *callee_span))
+        .collect::<Option<Vec<_>>>()?;
+    if any_nested(&calls) {
+        return None;
+    }
+    let call_spans = calls
+        .iter()
+        .map(|call_expr| call_expr.span)
+        .collect::<Vec<_>>();
+    let mut taken = HashMap::new();
+    let mut edits = vec![];
+    for call_expr in calls {
+        edits.extend(inline_call(
+            template,
+            call_expr,
+            &call_spans,
+            &mut taken,
+            semantic_result,
+            rope,
+        )?);
+    }
+    Some(edits)
+}
+
+/// The edits that inline one call: `let`s for non-trivial arguments and the body's statements
+/// go before the enclosing statement, and the returned value replaces the call.
+///
+/// `inlined` are the spans of all calls inlined by the same action, and `taken` holds the names
+/// already in use in each caller, keyed by the caller's start, so that calls in the same caller
+/// get distinct names.
+fn inline_call(
+    template: &InlineTemplate,
+    call_expr: &ExprCall,
+    inlined: &[Span],
+    taken: &mut HashMap<u32, Vec<String>>,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+) -> Option<Vec<TextEdit>> {
+    if call_expr.args.len() != template.params.len() {
+        return None;
+    }
+    let ancestors = ancestors_of_span(semantic_result.program.file(), call_expr.span);
+    let caller = ancestors.iter().find_map(|node| match node {
+        SyntaxNode::Function(function) => Some(*function),
+        _ => None,
+    })?;
+    let stmt = ancestors.windows(2).rev().find_map(|pair| match pair {
+        [SyntaxNode::Block(_), SyntaxNode::Stmt(stmt)] => Some(*stmt),
+        _ => None,
+    })?;
+    let stmt_start = stmt.span().start as usize;
+    let indent = indentation_at(stmt_start, rope);
+
+    let taken = taken
+        .entry(caller.span.start)
+        .or_insert_with(|| bindings_in(caller.span, semantic_result, rope));
+    let mut prelude = vec![];
+    let mut params = vec![];
+    for (arg, param) in call_expr.args.iter().zip(template.params.iter()) {
+        let arg_text = span_text(arg.span(), rope)?;
+        if matches!(arg, Expr::Name(_) | Expr::Literal(_)) {
+            params.push(arg_text);
+            continue;
+        }
+        let name = fresh_name(param, taken);
+        taken.push(name.clone());
+        prelude.push(format!("let {name} = {arg_text};"));
+        params.push(name);
+    }
+    let locals = template
+        .locals
+        .iter()
+        .map(|local| {
+            let name = fresh_name(local, taken);
+            taken.push(name.clone());
+            name
+        })
+        .collect::<Vec<_>>();
+    if !template.stmts.is_empty() {
+        let body = template.render(&template.stmts, &params, &locals);
+        prelude.push(
+            body.lines()
+                .map(|line| line.strip_prefix(template.indent.as_str()).unwrap_or(line))
+                .collect::<Vec<_>>()
+                .join(&format!("\n{indent}")),
+        );
+    }
+    let separator = format!("\n{indent}");
+    // Other calls being inlined, and calls in their arguments, are fine as long as what is left
+    // of them in place doesn't call anything, as their preludes are hoisted in order too.
+    let hoisted_too = |other: &ExprCall| {
+        !template.value_has_call
+            && inlined
+                .iter()
+                .any(|span| span.start <= other.span.start && other.span.end <= span.end)
+    };
+    if !prelude.is_empty() && runs_call_before(stmt, call_expr.span, hoisted_too) {
+        return None;
+    }
+
+    match template.value.as_ref() {
+        Some(value) => {
+            let mut value_text = template.render(value, &params, &locals);
+            let in_operand = matches!(
+                ancestors.iter().rev().nth(1),
+                Some(SyntaxNode::Expr(Expr::Binary(_) | Expr::Field(_)))
+            );
+            if template.value_is_binary && in_operand {
+                value_text = format!("({value_text})");
+            }
+            let mut edits = vec![];
+            if !prelude.is_empty() {
+                let insert_at = offset_to_position(stmt_start, rope)?;
+                edits.push(TextEdit::new(
+                    Range::new(insert_at, insert_at),
+                    format!("{}{separator}", prelude.join(&separator)),
+                ));
+            }
+            edits.push(TextEdit::new(
+                span_to_range(call_expr.span, rope)?,
+                value_text,
+            ));
+            Some(edits)
+        }
+        // Without a value, the call must be a statement of its own.
+        None => {
+            let Stmt::Expr(expr_stmt) = stmt else {
+                return None;
+            };
+            if expr_stmt.expr.as_deref().map(Expr::span) != Some(call_expr.span) {
+                return None;
+            }
+            if prelude.is_empty() {
+                return Some(vec![TextEdit::new(
+                    removal_range(stmt.span(), rope)?,
+                    String::new(),
+                )]);
+            }
+            Some(vec![TextEdit::new(
+                span_to_range(with_semicolon(stmt.span(), rope), rope)?,
+                prelude.join(&separator),
+            )])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{apply_changes, backend, Fixture};
+
+    const DOUBLE: &str = "fn double(x: int) -> int {\n    return x * 2;\n}\n";
+
+    fn inline(source: &str, title: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), title)
+    }
+
+    /// Runs "Inline all calls and delete" on the `$0` in `source`, with `others` open too.
+    fn inline_all(source: &str, others: &[(&str, &str)]) -> Option<Vec<String>> {
+        let fixture = Fixture::new(source);
+        let documents = fixture.with_others(others);
+        let (service, _socket) = backend(&documents);
+        let action = service
+            .inner()
+            .inline_all_action(&fixture.uri, fixture.selection.start)?;
+        Some(apply_changes(&documents, &action.edit?.changes?))
+    }
+
+    #[test]
+    fn inlines_one_call() {
+        assert_eq!(
+            inline(
+                &format!("{DOUBLE}fn f(a: int) -> int {{\n    return 1 + $0double(a + 1);\n}}\n"),
+                "Inline `double`"
+            )
+            .unwrap(),
+            format!(
+                "{DOUBLE}fn f(a: int) -> int {{\n    let x = a + 1;\n    return 1 + (x * 2);\n}}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn inlines_body_statements_with_fresh_locals() {
+        let source = "fn g(x: int) -> int {\n    let y = x + 1;\n    return y * y;\n}\n\
+                      fn f(y: int) -> int {\n    return $0g(y);\n}\n";
+        assert_eq!(
+            inline(source, "Inline `g`").unwrap(),
+            "fn g(x: int) -> int {\n    let y = x + 1;\n    return y * y;\n}\n\
+             fn f(y: int) -> int {\n    let y2 = y + 1;\n    return y2 * y2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn declines_when_an_earlier_call_would_run_after_the_hoisted_arguments() {
+        let source = format!(
+            "{DOUBLE}fn g(n: int) -> int {{\n    return n;\n}}\n\
+             fn f() -> int {{\n    return g(1) + $0double(g(2));\n}}\n"
+        );
+        assert_eq!(inline(&source, "Inline `double`"), None);
+    }
+
+    #[test]
+    fn declines_recursive_functions() {
+        let source = "fn r(n: int) -> int {\n    return r(n - 1);\n}\n\
+                      fn f() -> int {\n    return $0r(1);\n}\n";
+        assert_eq!(inline(source, "Inline `r`"), None);
+    }
+
+    #[test]
+    fn inline_all_gives_each_call_in_a_caller_its_own_names() {
+        let source = "fn $0double(x: int) -> int {\n    return x * 2;\n}\n\
+                      fn g(n: int) -> int {\n    return n;\n}\n\
+                      fn f() -> int {\n    let z = double(g(1)) + double(g(2));\n    return z;\n}\n";
+        assert_eq!(
+            inline_all(source, &[]).unwrap(),
+            ["fn g(n: int) -> int {\n    return n;\n}\n\
+              fn f() -> int {\n    let x = g(1);\n    let x2 = g(2);\n    \
+              let z = (x * 2) + (x2 * 2);\n    return z;\n}\n"]
+        );
+    }
+
+    #[test]
+    fn inline_all_updates_other_documents() {
+        let other = "fn h(a: int) -> int {\n    return double(a) + double(a);\n}\n";
+        let source = "fn $0double(x: int) -> int {\n    return x * 2;\n}\n";
+        let results = inline_all(source, &[("file:///other.l", other)]).unwrap();
+        assert_eq!(
+            results,
+            [
+                "",
+                "fn h(a: int) -> int {\n    return (a * 2) + (a * 2);\n}\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn inline_all_declines_nested_calls() {
+        let source = "fn $0double(x: int) -> int {\n    return x * 2;\n}\n\
+                      fn f() -> int {\n    return double(double(1));\n}\n";
+        assert!(inline_all(source, &[]).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+use l_lang::{Expr, Stmt, SymbolKind};
+use tower_lsp::lsp_types::*;
+
+use super::{removal_range, single_file_edit, ActionContext};
+use crate::syntax::{ancestors_of_span, walk, SyntaxNode};
+use crate::{span_text, span_to_range, symbol_at_offset};
+
+/// "Inline variable" on a `let` binding or one of its uses: every use is replaced with the
+/// initialiser and the declaration is removed.
+pub fn actions(ctx: &ActionContext) -> Vec<CodeAction> {
+    inline(ctx).into_iter().collect()
+}
+
+fn inline(ctx: &ActionContext) -> Option<CodeAction> {
+    let ActionContext {
+        uri,
+        semantic_result,
+        rope,
+        selection,
+        ..
+    } = *ctx;
+    let semantic = &semantic_result.semantic;
+    let file = semantic_result.program.file();
+    let symbol_id = symbol_at_offset(semantic_result, selection.start as usize)?;
+    if semantic.get_symbol_kind(symbol_id) != SymbolKind::Variable {
+        return None;
+    }
+    let name_span = semantic.get_symbol_span(symbol_id);
+    let let_stmt = ancestors_of_span(file, name_span)
+        .into_iter()
+        .find_map(|node| match node {
+            SyntaxNode::Stmt(Stmt::Let(let_stmt))
+                if let_stmt.name.as_ref().map(|name| name.span) == Some(name_span) =>
+            {
+                Some(let_stmt)
+            }
+            _ => None,
+        })?;
+    let init = let_stmt.init.as_deref()?;
+    let init_text = span_text(init.span(), rope)?;
+
+    let mut has_call = false;
+    let mut init_names = vec![];
+    walk(SyntaxNode::Expr(init), &mut |node| match node {
+        SyntaxNode::Expr(Expr::Call(_)) => has_call = true,
+        SyntaxNode::Expr(Expr::Name(name_expr)) => init_names.push(name_expr.name.as_str()),
+        _ => {}
+    });
+    let reference_spans = semantic
+        .get_symbol_references(symbol_id)
+        .iter()
+        .map(|ref_id| semantic.reference_spans[*ref_id])
+        .collect::<Vec<_>>();
+    // Calls may have side effects, so they must run exactly once: not duplicated, and not
+    // dropped along with an unused binding.
+    if has_call && reference_spans.len() != 1 {
+        return None;
+    }
+    // A binding declared between the `let` and a use could shadow a name the initialiser uses.
+    let last_use = reference_spans.iter().map(|span| span.end).max();
+    let shadowed = last_use.is_some_and(|last_use| {
+        semantic.symbol_spans.iter().any(|span| {
+            let_stmt.span.end <= span.start
+                && span.end <= last_use
+                && span_text(*span, rope).is_some_and(|text| init_names.contains(&text.as_str()))
+        })
+    });
+    if shadowed {
+        return None;
+    }
+
+    let needs_parens = matches!(init, Expr::Binary(_));
+    let mut edits = vec![TextEdit::new(
+        removal_range(let_stmt.span, rope)?,
+        String::new(),
+    )];
+    for span in reference_spans {
+        let ancestors = ancestors_of_span(file, span);
+        let in_operand = matches!(
+            ancestors.iter().rev().nth(1),
+            Some(SyntaxNode::Expr(Expr::Binary(_) | Expr::Field(_)))
+        );
+        let new_text = if needs_parens && in_operand {
+            format!("({init_text})")
+        } else {
+            init_text.clone()
+        };
+        edits.push(TextEdit::new(span_to_range(span, rope)?, new_text));
+    }
+    Some(CodeAction {
+        title: "Inline variable".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_INLINE),
+        edit: Some(single_file_edit(uri, edits)),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::Fixture;
+
+    fn inline(source: &str) -> Option<String> {
+        let fixture = Fixture::new(source);
+        fixture.apply(&actions(&fixture.ctx()), "Inline variable")
+    }
+
+    #[test]
+    fn inlines_every_use() {
+        assert_eq!(
+            inline("fn f(a: int) -> int {\n    let $0b = a + 1;\n    return b * b;\n}\n").unwrap(),
+            "fn f(a: int) -> int {\n    return (a + 1) * (a + 1);\n}\n"
+        );
+    }
+
+    #[test]
+    fn inlines_a_call_used_once() {
+        assert_eq!(
+            inline("fn g() -> int {\n    return 1;\n}\nfn f() -> int {\n    let b = g();\n    return $0b;\n}\n").unwrap(),
+            "fn g() -> int {\n    return 1;\n}\nfn f() -> int {\n    return g();\n}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_calls_that_would_run_twice_or_not_at_all() {
+        let g = "fn g() -> int {\n    return 1;\n}\n";
+        assert_eq!(
+            inline(&format!(
+                "{g}fn f() -> int {{\n    let $0b = g();\n    return b + b;\n}}\n"
+            )),
+            None
+        );
+        assert_eq!(
+            inline(&format!(
+                "{g}fn f() -> int {{\n    let $0b = g();\n    return 1;\n}}\n"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_initialisers_whose_names_are_shadowed() {
+        assert_eq!(
+            inline("fn f(a: int) -> int {\n    let $0b = a;\n    let a = 2;\n    return b;\n}\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use l_lang::{CompileResult, ExprCall, Item, Span};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use super::{
+    after_open_paren, any_nested, args_range, bindings_in, call_with_callee, contains_call,
+    fresh_name,
+};
+use crate::inlay_hints::params_end;
+use crate::{offset_to_position, span_text, span_to_range, Backend};
+
+/// The struct that replaces some of a function's parameters.
+struct ParameterObject {
+    struct_name: String,
+    /// Indices of the replaced parameters, in order.
+    replaced: Vec<usize>,
+    field_names: Vec<String>,
+}
+
+impl Backend {
+    /// "Introduce parameter object" on the selected parameters of a function declaration: they
+    /// become the fields of a new struct, the function takes one value of it instead, and calls
+    /// in the indexed documents pass a struct literal.
+    pub(super) fn parameter_object_action(&self, uri: &Url, selection: Span) -> Option<CodeAction> {
+        let key = uri.to_string();
+        let (function_name, object, edits) = {
+            let semantic_result = self.semanticast_map.get(&key)?;
+            let rope = self.document_map.get(&key)?;
+            let semantic = &semantic_result.semantic;
+            let file = semantic_result.program.file();
+            let function = file.items.iter().find_map(|item| match item {
+                Item::Function(function)
+                    if function.span.start <= selection.start
+                        && selection.end <= function.span.end =>
+                {
+                    Some(function)
+                }
+                _ => None,
+            })?;
+            let replaced = function
+                .params
+                .iter()
+                .enumerate()
+                .filter(|(_, param)| {
+                    param.span.start <= selection.end && selection.start <= param.span.end
+                })
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            if replaced.is_empty() {
+                return None;
+            }
+            let name = function.name.as_ref()?;
+            let fields = replaced
+                .iter()
+                .map(|index| {
+                    let param = &function.params[*index];
+                    Some((param.name.as_ref()?, param.ty.as_ref()?))
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            let item_names = file
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Function(function) => Some(function.name.as_ref()?.name.clone()),
+                    Item::Struct(struct_decl) => Some(struct_decl.name.as_ref()?.name.clone()),
+                })
+                .collect::<Vec<_>>();
+            let object = ParameterObject {
+                struct_name: fresh_name(&format!("{}Params", pascal_case(&name.name)), &item_names),
+                replaced,
+                field_names: fields.iter().map(|(name, _)| name.name.clone()).collect(),
+            };
+            let param_name = fresh_name(
+                "params",
+                &bindings_in(function.span, &semantic_result, &rope),
+            );
+
+            let mut edits = vec![];
+            let insert_at = offset_to_position(function.span.start as usize, &rope)?;
+            let struct_fields = fields
+                .iter()
+                .map(|(name, ty)| format!("    {}: {},\n", name.name, ty.name))
+                .collect::<String>();
+            edits.push(TextEdit::new(
+                Range::new(insert_at, insert_at),
+                format!("struct {} {{\n{struct_fields}}}\n\n", object.struct_name),
+            ));
+            let params = function
+                .params
+                .iter()
+                .enumerate()
+                .filter_map(|(index, param)| {
+                    if index == object.replaced[0] {
+                        Some(format!("{param_name}: {}", object.struct_name))
+                    } else if object.replaced.contains(&index) {
+                        None
+                    } else {
+                        span_text(param.span, &rope)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let params_span = Span {
+                start: after_open_paren(name.span.end, &rope)?,
+                end: params_end(function, &rope)? as u32 - 1,
+            };
+            edits.push(TextEdit::new(span_to_range(params_span, &rope)?, params));
+            for (param_name_node, _) in fields.iter() {
+                let param_id = semantic.get_symbol_at(param_name_node.span.start as usize)?;
+                for ref_id in semantic.get_symbol_references(param_id).iter() {
+                    edits.push(TextEdit::new(
+                        span_to_range(semantic.reference_spans[*ref_id], &rope)?,
+                        format!("{param_name}.{}", param_name_node.name),
+                    ));
+                }
+            }
+            let function_id = semantic.get_symbol_at(name.span.start as usize)?;
+            let callee_spans = semantic
+                .get_symbol_references(function_id)
+                .iter()
+                .map(|ref_id| semantic.reference_spans[*ref_id])
+                .collect::<Vec<_>>();
+            // The body's edits would fall inside a recursive call's argument edit.
+            if callee_spans.iter().any(|callee_span| {
+                function.span.start <= callee_span.start && callee_span.end <= function.span.end
+            }) {
+                return None;
+            }
+            edits.extend(call_edits(&object, &callee_spans, &semantic_result, &rope)?);
+            (name.name.clone(), object, edits)
+        };
+
+        let mut changes = HashMap::from([(uri.clone(), edits)]);
+        self.try_for_each_use_elsewhere::<()>(
+            &key,
+            &function_name,
+            |other_uri, semantic_result, rope, callee_spans| {
+                let edits = call_edits(&object, &callee_spans, semantic_result, rope).ok_or(())?;
+                if !edits.is_empty() {
+                    changes.insert(other_uri, edits);
+                }
+                Ok(())
+            },
+        )
+        .ok()?;
+        Some(CodeAction {
+            title: "Introduce parameter object".to_string(),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            edit: Some(WorkspaceEdit::new(changes)),
+            ..Default::default()
+        })
+    }
+}
+
+/// Passes the replaced arguments of every call whose callee is one of `callee_spans` as a
+/// struct literal. Fails if the calls are nested in each other or one can't be rewritten.
+fn call_edits(
+    object: &ParameterObject,
+    callee_spans: &[Span],
+    semantic_result: &CompileResult,
+    rope: &Rope,
+) -> Option<Vec<TextEdit>> {
+    let file = semantic_result.program.file();
+    let calls = callee_spans
+        .iter()
+        .map(|callee_span| call_with_callee(file, *callee_span))
+        .collect::<Option<Vec<_>>>()?;
+    if any_nested(&calls) {
+        return None;
+    }
+    calls
+        .into_iter()
+        .map(|call_expr| call_edit(object, call_expr, rope))
+        .collect()
+}
+
+/// Fails if the call would evaluate its arguments in a different order: when the replaced
+/// parameters aren't next to each other, the arguments between them move after the literal.
+fn call_edit(object: &ParameterObject, call_expr: &ExprCall, rope: &Rope) -> Option<TextEdit> {
+    let first = *object.replaced.first()?;
+    let last = *object.replaced.last()?;
+    let reordered = last - first + 1 != object.replaced.len();
+    if reordered && call_expr.args.get(first..=last)?.iter().any(contains_call) {
+        return None;
+    }
+    let literal_fields = object
+        .replaced
+        .iter()
+        .zip(object.field_names.iter())
+        .map(|(index, field_name)| {
+            Some(format!(
+                "{field_name}: {}",
+                span_text(call_expr.args.get(*index)?.span(), rope)?
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?
+        .join(", ");
+    let args = call_expr
+        .args
+        .iter()
+        .enumerate()
+        .filter_map(|(index, arg)| {
+            if index == object.replaced[0] {
+                Some(format!("{} {{ {literal_fields} }}", object.struct_name))
+            } else if object.replaced.contains(&index) {
+                None
+            } else {
+                span_text(arg.span(), rope)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(TextEdit::new(args_range(call_expr, rope)?, args))
+}
+
+/// `compute_area` becomes `ComputeArea`.
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use l_lang::Expr;
+
+    use super::*;
+    use crate::code_actions::test_support::{apply_changes, apply_edits, backend, Fixture};
+    use crate::syntax::{walk, SyntaxNode};
+
+    /// Runs the action on the parameters selected in `source`, with `others` open too.
+    fn introduce(source: &str, others: &[(&str, &str)]) -> Option<Vec<String>> {
+        let fixture = Fixture::new(source);
+        let documents = fixture.with_others(others);
+        let (service, _socket) = backend(&documents);
+        let action = service
+            .inner()
+            .parameter_object_action(&fixture.uri, fixture.selection)?;
+        Some(apply_changes(&documents, &action.edit?.changes?))
+    }
+
+    #[test]
+    fn replaces_parameters_at_every_call() {
+        let source = "fn area($0w: int, h: int$0) -> int {\n    return w * h;\n}\n\
+                      fn f() -> int {\n    return area(1, 2) + area(3, 4);\n}\n";
+        let other = "fn g() -> int {\n    return area(5, 6);\n}\n";
+        assert_eq!(
+            introduce(source, &[("file:///other.l", other)]).unwrap(),
+            [
+                "struct AreaParams {\n    w: int,\n    h: int,\n}\n\n\
+                 fn area(params: AreaParams) -> int {\n    return params.w * params.h;\n}\n\
+                 fn f() -> int {\n    return area(AreaParams { w: 1, h: 2 }) + \
+                 area(AreaParams { w: 3, h: 4 });\n}\n",
+                "fn g() -> int {\n    return area(AreaParams { w: 5, h: 6 });\n}\n",
+            ]
+        );
+    }
+
+    /// Rewrites the first call in `source` for an object replacing the parameters at `replaced`.
+    fn rewrite_call(source: &str, replaced: Vec<usize>) -> Option<String> {
+        let fixture = Fixture::new(source);
+        let mut calls = vec![];
+        for item in &fixture.semantic_result.program.file().items {
+            if let Item::Function(function) = item {
+                walk(SyntaxNode::Function(function), &mut |node| {
+                    if let SyntaxNode::Expr(Expr::Call(call_expr)) = node {
+                        calls.push(call_expr);
+                    }
+                });
+            }
+        }
+        let object = ParameterObject {
+            struct_name: "P".to_string(),
+            field_names: replaced.iter().map(|index| format!("p{index}")).collect(),
+            replaced,
+        };
+        let edit = call_edit(&object, calls.first()?, &fixture.rope)?;
+        Some(apply_edits(&fixture.text, &[edit]))
+    }
+
+    #[test]
+    fn moves_pure_arguments_between_replaced_parameters() {
+        let source = "$0fn g() -> int {\n    return f(1, 2, 3);\n}\n";
+        assert_eq!(
+            rewrite_call(source, vec![0, 2]).unwrap(),
+            "fn g() -> int {\n    return f(P { p0: 1, p2: 3 }, 2);\n}\n"
+        );
+    }
+
+    #[test]
+    fn declines_reordering_arguments_that_call_functions() {
+        let source = "$0fn g() -> int {\n    return f(h(), 2, 3);\n}\n";
+        assert_eq!(rewrite_call(source, vec![0, 2]), None);
+        assert!(rewrite_call(source, vec![0, 1]).is_some());
+    }
+
+    #[test]
+    fn declines_recursive_functions() {
+        let source = "fn r($0a: int$0) -> int {\n    return r(a - 1);\n}\n";
+        assert_eq!(introduce(source, &[]), None);
+    }
+
+    #[test]
+    fn declines_nested_calls() {
+        let source = "fn f($0a: int$0) -> int {\n    return a;\n}\n\
+                      fn g() -> int {\n    return f(f(1));\n}\n";
+        assert_eq!(introduce(source, &[]), None);
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use l_lang::{CompileResult, Expr, Item, Span, Struct};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use super::contains_call;
+use crate::syntax::{walk, SyntaxNode};
+use crate::{span_text, span_to_range, Backend};
+
+/// A field of a struct declaration or a struct literal.
+struct Entry {
+    name: String,
+    span: Span,
+}
+
+impl Backend {
+    /// On a struct declaration, "Sort fields alphabetically", and "Move field up/down" on one of
+    /// its fields. The declaration and every literal of the struct in the indexed documents are
+    /// rewritten; literals in other documents are the unresolved references to its name.
+    pub(super) fn reorder_fields_actions(&self, uri: &Url, selection: Span) -> Vec<CodeAction> {
+        self.reorder_fields(uri, selection).unwrap_or_default()
+    }
+
+    fn reorder_fields(&self, uri: &Url, selection: Span) -> Option<Vec<CodeAction>> {
+        let key = uri.to_string();
+        let (struct_name, mut reorders) = {
+            let semantic_result = self.semanticast_map.get(&key)?;
+            let rope = self.document_map.get(&key)?;
+            let struct_decl = semantic_result
+                .program
+                .file()
+                .items
+                .iter()
+                .find_map(|item| match item {
+                    Item::Struct(struct_decl)
+                        if struct_decl.span.start <= selection.start
+                            && selection.end <= struct_decl.span.end =>
+                    {
+                        Some(struct_decl)
+                    }
+                    _ => None,
+                })?;
+            let reorders = orders(struct_decl, selection)
+                .into_iter()
+                .filter_map(|(title, order)| {
+                    let edits = reorder_struct(&semantic_result, &rope, struct_decl, &order)?;
+                    Some((title, order, HashMap::from([(uri.clone(), edits)])))
+                })
+                .collect::<Vec<_>>();
+            (struct_decl.name.as_ref()?.name.clone(), reorders)
+        };
+
+        // Once no order is left, there is nothing more to look at.
+        let _ = self.try_for_each_use_elsewhere::<()>(
+            &key,
+            &struct_name,
+            |other_uri, semantic_result, rope, name_spans| {
+                // An order that can't be applied to every literal is not offered at all.
+                reorders.retain_mut(|(_, order, changes)| {
+                    let Some(edits) = literal_edits(semantic_result, rope, order, |name_span| {
+                        name_spans.contains(&name_span)
+                    }) else {
+                        return false;
+                    };
+                    if !edits.is_empty() {
+                        changes.insert(other_uri.clone(), edits);
+                    }
+                    true
+                });
+                if reorders.is_empty() {
+                    Err(())
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        Some(
+            reorders
+                .into_iter()
+                .map(|(title, _, changes)| CodeAction {
+                    title,
+                    kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                    edit: Some(WorkspaceEdit::new(changes)),
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The new field orders offered for a struct, with their titles.
+fn orders(struct_decl: &Struct, selection: Span) -> Vec<(String, Vec<String>)> {
+    let names = struct_decl
+        .fields
+        .iter()
+        .filter_map(|field| Some(field.name.as_ref()?.name.clone()))
+        .collect::<Vec<_>>();
+
+    let mut orders = vec![];
+    let mut sorted = names.clone();
+    sorted.sort();
+    if sorted != names {
+        orders.push(("Sort fields alphabetically".to_string(), sorted));
+    }
+    let selected = struct_decl
+        .fields
+        .iter()
+        .position(|field| field.span.start <= selection.start && selection.end <= field.span.end);
+    if let Some(index) = selected.filter(|index| *index < names.len()) {
+        if index > 0 {
+            let mut order = names.clone();
+            order.swap(index - 1, index);
+            orders.push((format!("Move field `{}` up", names[index]), order));
+        }
+        if index + 1 < names.len() {
+            let mut order = names.clone();
+            order.swap(index, index + 1);
+            orders.push((format!("Move field `{}` down", names[index]), order));
+        }
+    }
+    orders
+}
+
+/// The edits to the declaration and the literals of the struct in its own document.
+fn reorder_struct(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    struct_decl: &Struct,
+    order: &[String],
+) -> Option<Vec<TextEdit>> {
+    let semantic = &semantic_result.semantic;
+    let name = struct_decl.name.as_ref()?;
+    let struct_id = semantic.get_symbol_at(name.span.start as usize)?;
+    if !semantic.structs.contains_key(&struct_id) {
+        return None;
+    }
+
+    let entries = struct_decl
+        .fields
+        .iter()
+        .map(|field| {
+            Some(Entry {
+                name: field.name.as_ref()?.name.clone(),
+                span: field.span,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let mut edits = vec![reorder_edit(
+        &entries,
+        order,
+        name.span.end,
+        struct_decl.span.end,
+        rope,
+    )??];
+    edits.extend(literal_edits(semantic_result, rope, order, |name_span| {
+        semantic
+            .get_reference_at(name_span.start as usize)
+            .and_then(|ref_id| semantic.references[ref_id])
+            == Some(struct_id)
+    })?);
+    Some(edits)
+}
+
+/// The edits to the literals whose name `is_literal_of` accepts. Fails if one of them can't be
+/// rewritten, or if reordering it would change the order its initialisers' calls run in.
+fn literal_edits(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    order: &[String],
+    is_literal_of: impl Fn(Span) -> bool,
+) -> Option<Vec<TextEdit>> {
+    let mut literals = vec![];
+    walk(
+        SyntaxNode::File(semantic_result.program.file()),
+        &mut |node| {
+            if let SyntaxNode::Expr(Expr::Struct(struct_expr)) = node {
+                literals.push(struct_expr);
+            }
+        },
+    );
+    let mut edits = vec![];
+    for struct_expr in literals {
+        let Some(literal_name) = struct_expr.name.as_ref() else {
+            continue;
+        };
+        if !is_literal_of(literal_name.span) {
+            continue;
+        }
+        let entries = struct_expr
+            .fields
+            .iter()
+            .map(|field| {
+                Some(Entry {
+                    name: field.name.as_ref()?.name.clone(),
+                    span: field.span,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let Some(edit) = reorder_edit(
+            &entries,
+            order,
+            literal_name.span.end,
+            struct_expr.span.end,
+            rope,
+        )?
+        else {
+            continue;
+        };
+        let has_call = struct_expr
+            .fields
+            .iter()
+            .any(|field| field.value.as_deref().is_some_and(contains_call));
+        if has_call {
+            return None;
+        }
+        edits.push(edit);
+    }
+    Some(edits)
+}
+
+/// Rewrites the fields between the braces that follow `after` into `order`. Fields on lines of
+/// their own move together with the comments above and after them. Fields that share a line are
+/// joined with `, `.
+///
+/// `Some(None)` means the fields are already in order, and `None` that their layout isn't
+/// supported.
+fn reorder_edit(
+    entries: &[Entry],
+    order: &[String],
+    after: u32,
+    end: u32,
+    rope: &Rope,
+) -> Option<Option<TextEdit>> {
+    let rank = |entry: &Entry| {
+        order
+            .iter()
+            .position(|name| *name == entry.name)
+            .unwrap_or(usize::MAX)
+    };
+    let mut new_order = (0..entries.len()).collect::<Vec<_>>();
+    new_order.sort_by_key(|index| rank(&entries[*index]));
+    if new_order.iter().enumerate().all(|(i, index)| i == *index) {
+        return Some(None);
+    }
+    let (first, last) = (entries.first()?, entries.last()?);
+
+    let line_of = |offset: u32| rope.try_byte_to_line(offset as usize).ok();
+    let open_brace = after + span_text(Span { start: after, end }, rope)?.find('{')? as u32;
+    let close_brace = end.checked_sub(1)?;
+    let on_own_lines = line_of(open_brace)? < line_of(first.span.start)?
+        && line_of(last.span.end)? < line_of(close_brace)?
+        && entries
+            .windows(2)
+            .all(|pair| line_of(pair[0].span.end) < line_of(pair[1].span.start));
+
+    if !on_own_lines {
+        if line_of(first.span.start)? != line_of(last.span.end)? {
+            return None;
+        }
+        let text = new_order
+            .iter()
+            .map(|index| span_text(entries[*index].span, rope))
+            .collect::<Option<Vec<_>>>()?
+            .join(", ");
+        return Some(Some(TextEdit::new(
+            span_to_range(
+                Span {
+                    start: first.span.start,
+                    end: last.span.end,
+                },
+                rope,
+            )?,
+            text,
+        )));
+    }
+
+    // Each chunk runs from the end of the previous field's line to the end of its own line.
+    let line_end = |offset: u32| -> Option<u32> {
+        Some(rope.try_line_to_byte(line_of(offset)? + 1).ok()? as u32)
+    };
+    let region_start = rope.try_line_to_byte(line_of(open_brace)? + 1).ok()? as u32;
+    let mut chunk_start = region_start;
+    let mut chunks = vec![];
+    for entry in entries {
+        let chunk_end = line_end(entry.span.end)?;
+        let leading = span_text(
+            Span {
+                start: chunk_start,
+                end: entry.span.start,
+            },
+            rope,
+        )?;
+        let rest = span_text(
+            Span {
+                start: entry.span.end,
+                end: chunk_end,
+            },
+            rope,
+        )?;
+        let rest = rest.trim_end_matches(['\n', '\r']);
+        let trailing = rest.strip_prefix(',');
+        let trailer = trailing.unwrap_or(rest);
+        if !(trailer.trim().is_empty() || trailer.trim_start().starts_with("//")) {
+            return None;
+        }
+        chunks.push((
+            leading,
+            span_text(entry.span, rope)?,
+            trailer.to_string(),
+            trailing.is_some(),
+        ));
+        chunk_start = chunk_end;
+    }
+    let last_has_comma = chunks.last()?.3;
+
+    let mut text = String::new();
+    for (position, index) in new_order.iter().enumerate() {
+        let (leading, field, trailer, _) = &chunks[*index];
+        let comma = if position + 1 < new_order.len() || last_has_comma {
+            ","
+        } else {
+            ""
+        };
+        text.push_str(&format!("{leading}{field}{comma}{trailer}\n"));
+    }
+    Some(Some(TextEdit::new(
+        span_to_range(
+            Span {
+                start: region_start,
+                end: chunk_start,
+            },
+            rope,
+        )?,
+        text,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code_actions::test_support::{apply_changes, backend, Fixture};
+
+    const POINT: &str = "struct Point {\n    // Across.\n    y: int,\n    x: int, // Down.\n}\n";
+
+    /// Runs the action titled `title` at the `$0` in `source`, with `others` open too.
+    fn reorder(source: &str, others: &[(&str, &str)], title: &str) -> Option<Vec<String>> {
+        let fixture = Fixture::new(source);
+        let documents = fixture.with_others(others);
+        let (service, _socket) = backend(&documents);
+        let actions = service
+            .inner()
+            .reorder_fields_actions(&fixture.uri, fixture.selection);
+        let action = actions.into_iter().find(|action| action.title == title)?;
+        Some(apply_changes(&documents, &action.edit?.changes?))
+    }
+
+    #[test]
+    fn sorts_the_declaration_and_literals_in_every_document() {
+        let source =
+            format!("$0{POINT}fn f() -> Point {{\n    return Point {{ y: 1, x: 2 }};\n}}\n");
+        let other =
+            "fn g() -> Point {\n    return Point {\n        y: 3,\n        x: 4\n    };\n}\n";
+        assert_eq!(
+            reorder(
+                &source,
+                &[("file:///other.l", other)],
+                "Sort fields alphabetically"
+            )
+            .unwrap(),
+            [
+                "struct Point {\n    x: int, // Down.\n    // Across.\n    y: int,\n}\n\
+                 fn f() -> Point {\n    return Point { x: 2, y: 1 };\n}\n",
+                "fn g() -> Point {\n    return Point {\n        x: 4,\n        y: 3\n    };\n}\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_a_field() {
+        let source = POINT.replace("    x: int,", "    $0x: int,");
+        assert_eq!(
+            reorder(&source, &[], "Move field `x` up").unwrap(),
+            ["struct Point {\n    x: int, // Down.\n    // Across.\n    y: int,\n}\n"]
+        );
+    }
+
+    #[test]
+    fn leaves_literals_that_are_already_in_order() {
+        let source =
+            format!("$0{POINT}fn f() -> Point {{\n    return Point {{ x: 2, y: 1 }};\n}}\n");
+        let result = reorder(&source, &[], "Sort fields alphabetically").unwrap();
+        assert!(result[0].ends_with("return Point { x: 2, y: 1 };\n}\n"));
+    }
+
+    #[test]
+    fn declines_literals_it_cannot_rewrite() {
+        let other = "fn g() -> Point {\n    return Point { y: 3,\n        x: 4 };\n}\n";
+        assert_eq!(
+            reorder(
+                &format!("$0{POINT}"),
+                &[("file:///other.l", other)],
+                "Sort fields alphabetically"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn declines_reordering_initialisers_that_call_functions() {
+        let source = format!(
+            "$0{POINT}fn h() -> int {{\n    return 1;\n}}\n\
+             fn f() -> Point {{\n    return Point {{ y: h(), x: h() }};\n}}\n"
+        );
+        assert_eq!(reorder(&source, &[], "Sort fields alphabetically"), None);
+    }
+}
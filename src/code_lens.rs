@@ -0,0 +1,300 @@
+use l_lang::{CompileResult, Item};
+use ropey::Rope;
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::*;
+
+use crate::interpreter;
+use crate::{offset_to_position, span_to_range, Backend};
+
+/// The client command that opens the references peek view, provided by the VS Code extension.
+/// VS Code's own `editor.action.showReferences` needs VS Code objects rather than JSON arguments,
+/// so it is only used for clients that do not provide this command.
+pub const SHOW_REFERENCES_COMMAND: &str = "l.showReferences";
+pub const RUN_COMMAND: &str = "l.run";
+pub const TEST_COMMAND: &str = "l.test";
+
+impl Backend {
+    /// A reference count lens above every function and struct, plus "Run" above `main` and
+    /// "Test" above functions named `test_*` or `*_test`.
+    pub(crate) fn get_code_lenses(&self, uri: &Url) -> Option<Vec<CodeLens>> {
+        let key = uri.to_string();
+        let semantic_result = self.semanticast_map.get(&key)?;
+        let rope = self.document_map.get(&key)?;
+        let show_references = if self.supports_client_command(SHOW_REFERENCES_COMMAND) {
+            SHOW_REFERENCES_COMMAND
+        } else {
+            "editor.action.showReferences"
+        };
+
+        let mut lenses = vec![];
+        for item in semantic_result.program.file().items.iter() {
+            let (name, is_function) = match item {
+                Item::Function(function) => (function.name.as_ref(), true),
+                Item::Struct(struct_decl) => (struct_decl.name.as_ref(), false),
+            };
+            let Some(name) = name else {
+                continue;
+            };
+            let Some(range) = span_to_range(name.span, &rope) else {
+                continue;
+            };
+            if let Some(lens) = references_lens(
+                &semantic_result,
+                &rope,
+                uri,
+                name.span.start,
+                range,
+                show_references,
+            ) {
+                lenses.push(lens);
+            }
+            if !is_function {
+                continue;
+            }
+            let command = if name.name == "main" {
+                Some(("▶ Run", RUN_COMMAND))
+            } else if is_test_name(&name.name) {
+                Some(("▶ Test", TEST_COMMAND))
+            } else {
+                None
+            };
+            if let Some((title, command)) = command {
+                lenses.push(CodeLens {
+                    range,
+                    command: Some(Command {
+                        title: title.to_string(),
+                        command: command.to_string(),
+                        arguments: Some(vec![json!(uri), json!(name.name)]),
+                    }),
+                    data: None,
+                });
+            }
+        }
+        Some(lenses)
+    }
+
+    /// Handles the "Run" and "Test" lenses by evaluating the function. The target file must
+    /// compile without errors. A test passes when it runs to completion without returning `false`.
+    pub(crate) async fn run_function(&self, command: &str, arguments: Vec<Value>) {
+        let (message_type, message) = self.run_function_message(command, &arguments);
+        self.client.show_message(message_type, message).await;
+    }
+
+    /// The outcome of [`Backend::run_function`], as the message shown to the user.
+    fn run_function_message(&self, command: &str, arguments: &[Value]) -> (MessageType, String) {
+        let (Some(uri), Some(name)) = (
+            arguments.first().and_then(Value::as_str),
+            arguments.get(1).and_then(Value::as_str),
+        ) else {
+            return (MessageType::ERROR, format!("{command}: missing arguments"));
+        };
+        match self.semanticast_map.get(uri) {
+            None => (MessageType::ERROR, format!("{uri} is not open")),
+            Some(semantic_result) => {
+                let error_count =
+                    semantic_result.diagnostics.len() + semantic_result.semantic.errors.len();
+                let function = semantic_result
+                    .program
+                    .file()
+                    .items
+                    .iter()
+                    .find_map(|item| match item {
+                        Item::Function(function)
+                            if function
+                                .name
+                                .as_ref()
+                                .is_some_and(|function_name| function_name.name == name) =>
+                        {
+                            Some(function)
+                        }
+                        _ => None,
+                    });
+                match (error_count, function) {
+                    (0, Some(function)) => {
+                        let result = interpreter::run(&semantic_result, function);
+                        match (command == TEST_COMMAND, result) {
+                            (true, Ok(interpreter::Value::Bool(false))) => (
+                                MessageType::ERROR,
+                                format!("test `{name}` failed: it returned false"),
+                            ),
+                            (true, Ok(_)) => (MessageType::INFO, format!("test `{name}` passed")),
+                            (true, Err(err)) => {
+                                (MessageType::ERROR, format!("test `{name}` failed: {err}"))
+                            }
+                            (false, Ok(value)) => {
+                                (MessageType::INFO, format!("`{name}` returned {value}"))
+                            }
+                            (false, Err(err)) => {
+                                (MessageType::ERROR, format!("`{name}` failed: {err}"))
+                            }
+                        }
+                    }
+                    (0, None) => (MessageType::ERROR, format!("there is no function `{name}`")),
+                    (count, _) => (
+                        MessageType::ERROR,
+                        format!("cannot run `{name}`: the program has {count} error(s)"),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+fn is_test_name(name: &str) -> bool {
+    name.starts_with("test_") || name.ends_with("_test")
+}
+
+fn references_lens(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    uri: &Url,
+    name_offset: u32,
+    range: Range,
+    show_references: &str,
+) -> Option<CodeLens> {
+    let symbol_id = semantic_result
+        .semantic
+        .get_symbol_at(name_offset as usize)?;
+    let ref_ids = semantic_result.semantic.get_symbol_references(symbol_id);
+    let locations = ref_ids
+        .iter()
+        .filter_map(|ref_id| {
+            let span = semantic_result.semantic.reference_spans[*ref_id];
+            Some(Location::new(uri.clone(), span_to_range(span, rope)?))
+        })
+        .collect::<Vec<_>>();
+    let title = match locations.len() {
+        1 => "1 reference".to_string(),
+        count => format!("{count} references"),
+    };
+    Some(CodeLens {
+        range,
+        command: Some(Command {
+            title,
+            command: show_references.to_string(),
+            arguments: Some(vec![
+                json!(uri),
+                json!(offset_to_position(name_offset as usize, rope)?),
+                json!(locations),
+            ]),
+        }),
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, URI};
+
+    const SOURCE: &str = "struct Point {\n    x: int,\n}\n\n\
+                          fn make() -> Point {\n    return Point { x: 1 };\n}\n\n\
+                          fn main() -> int {\n    return make().x + make().x;\n}\n\n\
+                          fn test_make() {\n    make();\n}\n";
+
+    /// The `(line, title, command)` of every lens in `source`, for a client that provides
+    /// `client_commands`.
+    fn lenses(source: &str, client_commands: &[&str]) -> Vec<(u32, String, String)> {
+        let (service, _socket) = backend(&[(URI, source)]);
+        let backend = service.inner();
+        let capabilities = ClientCapabilities {
+            experimental: Some(json!({ "commands": client_commands })),
+            ..Default::default()
+        };
+        backend.client_capabilities.set(capabilities).unwrap();
+        backend
+            .get_code_lenses(&Url::parse(URI).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|lens| {
+                let command = lens.command.unwrap();
+                (lens.range.start.line, command.title, command.command)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_references_and_offers_run_and_test() {
+        assert_eq!(
+            lenses(SOURCE, &[SHOW_REFERENCES_COMMAND]),
+            [
+                (
+                    0,
+                    "2 references".to_string(),
+                    SHOW_REFERENCES_COMMAND.to_string()
+                ),
+                (
+                    4,
+                    "3 references".to_string(),
+                    SHOW_REFERENCES_COMMAND.to_string()
+                ),
+                (
+                    8,
+                    "0 references".to_string(),
+                    SHOW_REFERENCES_COMMAND.to_string()
+                ),
+                (8, "▶ Run".to_string(), RUN_COMMAND.to_string()),
+                (
+                    12,
+                    "0 references".to_string(),
+                    SHOW_REFERENCES_COMMAND.to_string()
+                ),
+                (12, "▶ Test".to_string(), TEST_COMMAND.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_editor_command_for_other_clients() {
+        let source = "fn f() {\n}\n";
+        assert_eq!(
+            lenses(source, &[]),
+            [(
+                0,
+                "0 references".to_string(),
+                "editor.action.showReferences".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_what_running_a_function_returned() {
+        let (service, _socket) = backend(&[(URI, SOURCE)]);
+        let backend = service.inner();
+        let run = |command, name| backend.run_function_message(command, &[json!(URI), json!(name)]);
+        assert_eq!(
+            run(RUN_COMMAND, "main"),
+            (MessageType::INFO, "`main` returned 2".to_string())
+        );
+        assert_eq!(
+            run(TEST_COMMAND, "test_make"),
+            (MessageType::INFO, "test `test_make` passed".to_string())
+        );
+        assert_eq!(
+            run(RUN_COMMAND, "missing"),
+            (
+                MessageType::ERROR,
+                "there is no function `missing`".to_string()
+            )
+        );
+        assert_eq!(
+            backend.run_function_message(RUN_COMMAND, &[]),
+            (MessageType::ERROR, "l.run: missing arguments".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_tests_that_return_false() {
+        let source = "fn test_false() -> bool {\n    return false;\n}\n";
+        let (service, _socket) = backend(&[(URI, source)]);
+        assert_eq!(
+            service
+                .inner()
+                .run_function_message(TEST_COMMAND, &[json!(URI), json!("test_false")]),
+            (
+                MessageType::ERROR,
+                "test `test_false` failed: it returned false".to_string()
+            )
+        );
+    }
+}
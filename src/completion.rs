@@ -0,0 +1,714 @@
+use l_lang::{
+    find_node_at_offset, AstNode, CompileResult, Expr, ExprField, File, Span, Stmt, SymbolId,
+    SymbolKind, Type,
+};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+use crate::documentation::{field_documentation, symbol_documentation};
+use crate::last_good::LastGoodAnalysis;
+use crate::syntax::{ancestors_of_span, find_function_by_name_span, is_ident_char, SyntaxNode};
+use crate::types::{expected_type_at, function_return_type};
+use crate::{offset_to_position, position_to_offset, span_text, Backend};
+
+/// Carried in `CompletionItem::data` so that `completionItem/resolve` can find the symbol again.
+#[derive(Debug, Serialize, Deserialize)]
+struct CompletionData {
+    uri: String,
+    /// Start offset of the symbol's declaration, or of the owning struct for fields.
+    symbol_offset: u32,
+    field: Option<String>,
+}
+
+impl Backend {
+    pub(crate) fn get_completion(&self, params: CompletionParams) -> Option<Vec<CompletionItem>> {
+        let text_doc_position = params.text_document_position;
+        let uri = text_doc_position.text_document.uri.to_string();
+        let semantic_result = self.semanticast_map.get(&uri)?;
+        let rope = self.document_map.get(&uri)?;
+        let offset = position_to_offset(text_doc_position.position, &rope)?;
+        let (replace_range, prefix) = word_range_at(&rope, text_doc_position.position)?;
+
+        let mut candidates = Vec::new();
+
+        let field_expr = match find_node_at_offset(semantic_result.program.file(), offset as u32) {
+            Some(AstNode::ExprField(field_expr)) => Some(field_expr),
+            _ => None,
+        };
+        let receiver = receiver_before_dot(&rope, replace_range.start);
+
+        if let Some(field_expr) = field_expr {
+            // Field access completion: suggest available fields/members
+            match self.get_struct_id_from_field(field_expr, &semantic_result, &rope) {
+                Some(struct_id) => {
+                    candidates.extend(field_candidates(&uri, &semantic_result, struct_id))
+                }
+                None => {
+                    if let Some((chain, _)) = receiver.as_ref() {
+                        candidates.extend(self.last_good_field_candidates(&uri, offset, chain));
+                    }
+                }
+            }
+            if let Some((receiver_span, statement)) =
+                postfix_receiver(semantic_result.program.file(), field_expr)
+            {
+                if let (Some(receiver_text), Some(receiver_start)) = (
+                    span_text(receiver_span, &rope),
+                    offset_to_position(receiver_span.start as usize, &rope),
+                ) {
+                    candidates.extend(postfix_candidates(
+                        &receiver_text,
+                        receiver_start,
+                        statement,
+                        &semantic_result,
+                        &rope,
+                        replace_range,
+                    ));
+                }
+            }
+        } else if let Some((chain, receiver_start)) = receiver {
+            // The current parse has no field access here, e.g. a trailing `p.` that broke the
+            // statement, so resolve the receiver against the last good analysis instead.
+            candidates.extend(self.last_good_field_candidates(&uri, offset, &chain));
+            // Without a tree, the receiver is taken to be a whole statement when nothing precedes
+            // it on its line.
+            let statement = rope
+                .get_line(receiver_start.line as usize)
+                .is_some_and(|line| {
+                    line.chars()
+                        .take(receiver_start.character as usize)
+                        .all(char::is_whitespace)
+                });
+            candidates.extend(postfix_candidates(
+                &chain.join("."),
+                receiver_start,
+                statement,
+                &semantic_result,
+                &rope,
+                replace_range,
+            ));
+        } else {
+            // Default: suggest all available symbols
+            candidates.extend(symbol_candidates(&uri, &semantic_result, &rope));
+            if !semantic_result.diagnostics.is_empty() {
+                let fallback = self.last_good_symbol_candidates(&uri, &candidates);
+                candidates.extend(fallback);
+            }
+        }
+
+        let expected_type = expected_type_at(&semantic_result, &rope, offset);
+        let items = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let score = fuzzy_score(&prefix, &candidate.label)?;
+                let type_rank = match (&expected_type, &candidate.ty) {
+                    (Some(expected), Some(ty)) if expected == ty => 0,
+                    _ => 1,
+                };
+                let insert_text_format = candidate
+                    .snippet
+                    .as_ref()
+                    .map(|_| InsertTextFormat::SNIPPET);
+                Some(CompletionItem {
+                    sort_text: Some(format!(
+                        "{}{:04}{}",
+                        type_rank,
+                        MAX_SCORE - score.min(MAX_SCORE),
+                        candidate.label
+                    )),
+                    filter_text: Some(candidate.label.clone()),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                        range: replace_range,
+                        new_text: candidate.snippet.unwrap_or_else(|| candidate.label.clone()),
+                    })),
+                    insert_text_format,
+                    additional_text_edits: candidate.additional_text_edits,
+                    label: candidate.label,
+                    kind: candidate.kind,
+                    detail: candidate.detail,
+                    data: candidate
+                        .data
+                        .and_then(|data| serde_json::to_value(data).ok()),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Some(items)
+    }
+
+    pub(crate) fn resolve_completion(&self, mut item: CompletionItem) -> CompletionItem {
+        let documentation = item
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value::<CompletionData>(data).ok())
+            .and_then(|data| self.completion_documentation(data));
+        if let Some(value) = documentation {
+            item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }));
+        }
+        item
+    }
+
+    fn last_good_field_candidates(
+        &self,
+        uri: &str,
+        offset: usize,
+        chain: &[String],
+    ) -> Vec<Candidate> {
+        let Some(last_good) = self.last_good_map.get(uri) else {
+            return vec![];
+        };
+        let Some(struct_id) = last_good.struct_for_chain(offset, chain) else {
+            return vec![];
+        };
+        let mut candidates = field_candidates(uri, &last_good.result, struct_id);
+        remap_to_current(&last_good, &mut candidates);
+        candidates
+    }
+
+    /// Symbols from the last good analysis that the current, broken parse lost.
+    fn last_good_symbol_candidates(&self, uri: &str, existing: &[Candidate]) -> Vec<Candidate> {
+        let Some(last_good) = self.last_good_map.get(uri) else {
+            return vec![];
+        };
+        let mut candidates = symbol_candidates(uri, &last_good.result, &last_good.rope)
+            .into_iter()
+            .filter(|candidate| {
+                !existing
+                    .iter()
+                    .any(|existing| existing.label == candidate.label)
+            })
+            .collect::<Vec<_>>();
+        remap_to_current(&last_good, &mut candidates);
+        candidates
+    }
+
+    fn completion_documentation(&self, data: CompletionData) -> Option<String> {
+        let semantic_result = self.semanticast_map.get(&data.uri)?;
+        let rope = self.document_map.get(&data.uri)?;
+        let uri = Url::parse(&data.uri).ok()?;
+        let symbol_id = semantic_result
+            .semantic
+            .get_symbol_at(data.symbol_offset as usize)?;
+        match data.field {
+            Some(field_name) => {
+                field_documentation(&semantic_result, &rope, &uri, symbol_id, &field_name)
+            }
+            None => symbol_documentation(&semantic_result, &rope, &uri, symbol_id),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Candidate {
+    label: String,
+    kind: Option<CompletionItemKind>,
+    detail: Option<String>,
+    /// The type this candidate evaluates to, used to rank it against the expected type.
+    ty: Option<Type>,
+    /// Snippet inserted in place of the label, used by postfix templates.
+    snippet: Option<String>,
+    additional_text_edits: Option<Vec<TextEdit>>,
+    data: Option<CompletionData>,
+}
+
+fn field_candidates(
+    uri: &str,
+    semantic_result: &CompileResult,
+    struct_id: SymbolId,
+) -> Vec<Candidate> {
+    let Some(struct_def) = semantic_result.semantic.structs.get(&struct_id) else {
+        return vec![];
+    };
+    let struct_offset = semantic_result.semantic.get_symbol_span(struct_id).start;
+    struct_def
+        .fields
+        .iter()
+        .map(|field| Candidate {
+            label: field.name.clone(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: Some(format!(
+                ": {}",
+                field.ty.format_literal_type(&semantic_result.semantic)
+            )),
+            ty: Some(field.ty.clone()),
+            data: Some(CompletionData {
+                uri: uri.to_string(),
+                symbol_offset: struct_offset,
+                field: Some(field.name.clone()),
+            }),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The expression a postfix template typed as `field_expr` applies to, and whether it is the whole
+/// expression of an expression statement. That is the receiver of `field_expr` extended over the
+/// operators it is the last operand of, so that `a + p.x.let` binds `a + p.x`.
+fn postfix_receiver(file: &File, field_expr: &ExprField) -> Option<(Span, bool)> {
+    let object_end = field_expr.object.as_deref()?.span().end;
+    let ancestors = ancestors_of_span(file, field_expr.span);
+    let mut index = ancestors.len() - 1;
+    while index > 0 {
+        match ancestors[index - 1] {
+            SyntaxNode::Expr(Expr::Binary(binary_expr))
+                if binary_expr.span.end == field_expr.span.end =>
+            {
+                index -= 1;
+            }
+            _ => break,
+        }
+    }
+    let statement = matches!(
+        ancestors.get(index.checked_sub(1)?),
+        Some(SyntaxNode::Stmt(Stmt::Expr(_)))
+    );
+    let start = ancestors[index].span().start;
+    Some((
+        Span {
+            start,
+            end: object_end,
+        },
+        statement,
+    ))
+}
+
+/// Postfix templates such as `expr.let`, offered after `.` on any receiver. The receiver and the
+/// dot are removed through `additional_text_edits` while the template body replaces the typed word.
+/// `.let` and `.return` are only offered when the receiver is a whole statement, as they produce
+/// statements.
+fn postfix_candidates(
+    receiver_text: &str,
+    receiver_start: Position,
+    statement: bool,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    replace_range: Range,
+) -> Vec<Candidate> {
+    let receiver_text = escape_snippet(receiver_text);
+
+    let functions = semantic_result
+        .semantic
+        .symbol_spans
+        .iter_enumerated()
+        .filter(|(symbol_id, _)| {
+            semantic_result.semantic.get_symbol_kind(*symbol_id) == SymbolKind::Function
+        })
+        .filter_map(|(_, span)| span_text(*span, rope))
+        .collect::<Vec<_>>();
+    let function_placeholder = if functions.is_empty() {
+        "${1:function}".to_string()
+    } else {
+        format!("${{1|{}|}}", functions.join(","))
+    };
+
+    let templates = [
+        (
+            "let",
+            "let name = expr;",
+            format!("let ${{1:name}} = {receiver_text};$0"),
+        ),
+        (
+            "return",
+            "return expr;",
+            format!("return {receiver_text};$0"),
+        ),
+        (
+            "arg",
+            "function(expr)",
+            format!("{function_placeholder}({receiver_text})$0"),
+        ),
+    ];
+    templates
+        .into_iter()
+        .filter(|(label, _, _)| statement || *label == "arg")
+        .map(|(label, detail, snippet)| Candidate {
+            label: label.to_string(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some(detail.to_string()),
+            snippet: Some(snippet),
+            additional_text_edits: Some(vec![TextEdit {
+                range: Range::new(receiver_start, replace_range.start),
+                new_text: String::new(),
+            }]),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Points the resolve data of candidates built from the last good analysis at the current text.
+fn remap_to_current(last_good: &LastGoodAnalysis, candidates: &mut [Candidate]) {
+    for candidate in candidates.iter_mut() {
+        candidate.data = candidate.data.take().and_then(|mut data| {
+            data.symbol_offset = last_good.edit.map_to_new(data.symbol_offset as usize)? as u32;
+            Some(data)
+        });
+    }
+}
+
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('$', "\\$")
+        .replace('}', "\\}")
+}
+
+fn symbol_candidates(uri: &str, semantic_result: &CompileResult, rope: &Rope) -> Vec<Candidate> {
+    let bindings = &semantic_result.semantic.bindings;
+    bindings
+        .iter_enumerated()
+        .filter_map(|(symbol_id, type_info)| {
+            let symbol_kind = semantic_result.semantic.get_symbol_kind(symbol_id);
+            let span = semantic_result.semantic.get_symbol_span(symbol_id);
+            let name = span_text(span, rope)?;
+
+            let (kind, detail, ty) = match symbol_kind {
+                SymbolKind::Variable => (
+                    Some(CompletionItemKind::VARIABLE),
+                    Some(format!(
+                        ": {}",
+                        type_info.ty.format_literal_type(&semantic_result.semantic)
+                    )),
+                    Some(type_info.ty.clone()),
+                ),
+                SymbolKind::Parameter => (None, None, Some(type_info.ty.clone())),
+                SymbolKind::Function => {
+                    let ty = find_function_by_name_span(semantic_result.program.file(), span)
+                        .and_then(|function| function_return_type(function, semantic_result, rope));
+                    (Some(CompletionItemKind::FUNCTION), None, ty)
+                }
+                SymbolKind::Struct => (
+                    Some(CompletionItemKind::STRUCT),
+                    None,
+                    Some(Type::Struct(symbol_id)),
+                ),
+                _ => (None, None, None),
+            };
+
+            Some(Candidate {
+                label: name,
+                kind,
+                detail,
+                ty,
+                data: Some(CompletionData {
+                    uri: uri.to_string(),
+                    symbol_offset: span.start,
+                    field: None,
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+const MAX_SCORE: u32 = 9999;
+
+/// Scores `candidate` against the typed `pattern` as a case-insensitive subsequence, favouring
+/// prefix matches, consecutive characters and matches at `_` word boundaries.
+/// Returns `None` when the pattern does not match at all.
+fn fuzzy_score(pattern: &str, candidate: &str) -> Option<u32> {
+    let candidate_chars = candidate.chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut next_index = 0;
+    let mut prev_match: Option<usize> = None;
+    for pattern_char in pattern.chars() {
+        let index = (next_index..candidate_chars.len())
+            .find(|i| candidate_chars[*i].eq_ignore_ascii_case(&pattern_char))?;
+        score += 1;
+        if candidate_chars[index] == pattern_char {
+            score += 1;
+        }
+        if index == 0 || prev_match.is_some_and(|prev| prev + 1 == index) {
+            score += 4;
+        } else if candidate_chars[index - 1] == '_' {
+            score += 3;
+        }
+        prev_match = Some(index);
+        next_index = index + 1;
+    }
+    if candidate
+        .to_lowercase()
+        .starts_with(&pattern.to_lowercase())
+    {
+        score += 20;
+    }
+    Some(score)
+}
+
+/// The `a.b` chain of plain names directly before the `.` that precedes `word_start`, and the
+/// position where it starts.
+fn receiver_before_dot(rope: &Rope, word_start: Position) -> Option<(Vec<String>, Position)> {
+    let line = rope.get_line(word_start.line as usize)?;
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut end = (word_start.character as usize).min(chars.len());
+    let mut chain = vec![];
+    while end > 0 && chars[end - 1] == '.' {
+        let dot = end - 1;
+        let start = chars[..dot]
+            .iter()
+            .rposition(|c| !is_ident_char(*c))
+            .map_or(0, |i| i + 1);
+        if start == dot {
+            break;
+        }
+        chain.push(chars[start..dot].iter().collect::<String>());
+        end = start;
+    }
+    if chain.is_empty() {
+        return None;
+    }
+    chain.reverse();
+    Some((chain, Position::new(word_start.line, end as u32)))
+}
+
+/// The range of the identifier around `position` and the part of it typed before the cursor,
+/// so that accepting a completion replaces the whole word instead of duplicating it.
+fn word_range_at(rope: &Rope, position: Position) -> Option<(Range, String)> {
+    let line = rope.get_line(position.line as usize)?;
+    let chars = line.chars().collect::<Vec<_>>();
+    let cursor = (position.character as usize).min(chars.len());
+    let start = chars[..cursor]
+        .iter()
+        .rposition(|c| !is_ident_char(*c))
+        .map_or(0, |i| i + 1);
+    let end = chars[cursor..]
+        .iter()
+        .position(|c| !is_ident_char(*c))
+        .map_or(chars.len(), |i| cursor + i);
+    let range = Range::new(
+        Position::new(position.line, start as u32),
+        Position::new(position.line, end as u32),
+    );
+    Some((range, chars[start..cursor].iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, Fixture};
+    use l_lang::compile;
+
+    /// The items `backend` completes at the cursor of `fixture`.
+    fn completion_items(backend: &Backend, fixture: &Fixture) -> Vec<CompletionItem> {
+        backend
+            .get_completion(CompletionParams {
+                text_document_position: fixture.position_params(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The labels completed at the `$0` cursor in `marked`, best ranked first.
+    fn complete(marked: &str) -> Vec<String> {
+        let fixture = Fixture::new(marked);
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        let mut items = completion_items(service.inner(), &fixture);
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        items.into_iter().map(|item| item.label).collect()
+    }
+
+    #[test]
+    fn ranks_candidates_of_the_expected_type_first() {
+        let labels = complete(
+            "fn f(name: string, count: int) -> int {\n    let total = count;\n    return ($0total);\n}\n",
+        );
+        let rank = |label: &str| labels.iter().position(|l| l == label).unwrap();
+        assert!(rank("count") < rank("name"));
+        assert!(rank("total") < rank("name"));
+        assert!(rank("f") < rank("name"));
+    }
+
+    #[test]
+    fn filters_candidates_by_the_typed_prefix() {
+        let labels = complete(
+            "fn f(name: string, count: int, cnt_max: int) -> int {\n    return cnt$0;\n}\n",
+        );
+        assert_eq!(labels, ["cnt_max", "count"]);
+    }
+
+    const NESTED: &str = "struct Inner {\n    v: int,\n}\n\nstruct Outer {\n    inner: Inner,\n    n: int,\n}\n\n\
+                          fn make(n: int) -> Outer {\n    return Outer { inner: Inner { v: n }, n: n };\n}\n\n\
+                          fn wrap(n: int) {\n    return make(n);\n}\n\n";
+
+    /// The field labels completed at the cursor, leaving out postfix templates.
+    fn fields(marked: &str) -> Vec<String> {
+        let mut labels = complete(&format!("{NESTED}{marked}"));
+        labels.retain(|label| !["let", "return", "arg"].contains(&label.as_str()));
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn completes_fields_of_call_results() {
+        assert_eq!(
+            fields("fn f() -> int {\n    return make(1).$0n;\n}\n"),
+            ["inner", "n"]
+        );
+        assert_eq!(
+            fields("fn f() -> int {\n    return wrap(1).$0n;\n}\n"),
+            ["inner", "n"]
+        );
+    }
+
+    #[test]
+    fn completes_fields_of_nested_expressions() {
+        assert_eq!(
+            fields("fn f(o: Outer) -> int {\n    return make(o.n).inner.$0v;\n}\n"),
+            ["v"]
+        );
+        assert_eq!(
+            fields("fn f(o: Outer) -> int {\n    return (o).inner.$0v;\n}\n"),
+            ["v"]
+        );
+        assert_eq!(
+            fields("fn f(o: Outer) -> int {\n    return Outer { inner: o.inner, n: 1 }.$0n;\n}\n"),
+            ["inner", "n"]
+        );
+    }
+
+    #[test]
+    fn resolving_an_item_adds_its_documentation() {
+        let fixture = Fixture::new(
+            "// Doubles `a`.\nfn double(a: int) -> int {\n    return a * 2;\n}\n\n\
+             fn f() -> int {\n    return dou$0ble(1);\n}\n",
+        );
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        let backend = service.inner();
+        let item = completion_items(backend, &fixture)
+            .into_iter()
+            .find(|item| item.label == "double")
+            .unwrap();
+        assert!(item.documentation.is_none());
+        let Some(Documentation::MarkupContent(content)) =
+            backend.resolve_completion(item).documentation
+        else {
+            panic!("no documentation");
+        };
+        assert!(content
+            .value
+            .starts_with("```l\nfn double(a: int) -> int\n```\n\n---\n\nDoubles `a`."));
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_prefixes_boundaries_and_runs() {
+        assert_eq!(fuzzy_score("xyz", "count"), None);
+        assert_eq!(fuzzy_score("tc", "count"), None);
+        assert!(fuzzy_score("co", "count") > fuzzy_score("co", "a_count"));
+        assert!(fuzzy_score("mx", "max_x") < fuzzy_score("mx", "m_x"));
+        assert!(fuzzy_score("cnt", "count") < fuzzy_score("cnt", "cnt_max"));
+        assert!(fuzzy_score("Co", "Count") > fuzzy_score("Co", "count"));
+        assert_eq!(fuzzy_score("", "count"), fuzzy_score("", "x"));
+    }
+
+    #[test]
+    fn word_range_covers_the_whole_identifier_around_the_cursor() {
+        let rope = Rope::from_str("fn f() {\n    total_count + 1;\n}\n");
+        assert_eq!(
+            word_range_at(&rope, Position::new(1, 9)),
+            Some((
+                Range::new(Position::new(1, 4), Position::new(1, 15)),
+                "total".to_string()
+            ))
+        );
+        assert_eq!(
+            word_range_at(&rope, Position::new(1, 16)),
+            Some((
+                Range::new(Position::new(1, 16), Position::new(1, 16)),
+                String::new()
+            ))
+        );
+    }
+
+    #[test]
+    fn word_range_stops_at_characters_the_lexer_does_not_accept() {
+        let rope = Rope::from_str("    ab\u{e9}cd\n");
+        assert_eq!(
+            word_range_at(&rope, Position::new(0, 9)),
+            Some((
+                Range::new(Position::new(0, 7), Position::new(0, 9)),
+                "cd".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn receiver_before_dot_reads_the_chain_of_names() {
+        let rope = Rope::from_str("    x + a.b_2.c\n");
+        assert_eq!(
+            receiver_before_dot(&rope, Position::new(0, 14)),
+            Some((
+                vec!["a".to_string(), "b_2".to_string()],
+                Position::new(0, 8)
+            ))
+        );
+        assert_eq!(receiver_before_dot(&rope, Position::new(0, 8)), None);
+        let rope = Rope::from_str("    f().x.\n");
+        assert_eq!(
+            receiver_before_dot(&rope, Position::new(0, 10)),
+            Some((vec!["x".to_string()], Position::new(0, 8)))
+        );
+    }
+
+    /// The receiver text of the postfix template typed at the first `le` after `marker`, and
+    /// whether `.let` and `.return` are offered for it.
+    fn postfix_at(source: &str, marker: &str) -> Option<(String, bool)> {
+        let semantic_result = compile(source);
+        let rope = Rope::from_str(source);
+        let offset = source.find(marker)? + marker.find(".le")? + 1;
+        let Some(AstNode::ExprField(field_expr)) =
+            find_node_at_offset(semantic_result.program.file(), offset as u32)
+        else {
+            return None;
+        };
+        let (span, statement) = postfix_receiver(semantic_result.program.file(), field_expr)?;
+        Some((span_text(span, &rope)?, statement))
+    }
+
+    const SOURCE: &str = "struct P {\n    x: int,\n}\n\nfn g(a: int) -> int {\n    return a;\n}\n\n\
+                          fn f(a: int, p: P) {\n    a + p.x.le;\n    g(p.x.le);\n    p.x.le * 2;\n}\n";
+
+    #[test]
+    fn postfix_templates_take_the_whole_statement_expression() {
+        assert_eq!(
+            postfix_at(SOURCE, "a + p.x.le"),
+            Some(("a + p.x".to_string(), true))
+        );
+    }
+
+    #[test]
+    fn postfix_templates_inside_expressions_only_wrap_the_operand() {
+        assert_eq!(
+            postfix_at(SOURCE, "g(p.x.le)"),
+            Some(("p.x".to_string(), false))
+        );
+        assert_eq!(
+            postfix_at(SOURCE, "p.x.le * 2"),
+            Some(("p.x".to_string(), false))
+        );
+    }
+
+    #[test]
+    fn statement_templates_are_only_offered_for_statements() {
+        let semantic_result = compile(SOURCE);
+        let rope = Rope::from_str(SOURCE);
+        let labels = |statement| {
+            postfix_candidates(
+                "p.x",
+                Position::new(0, 0),
+                statement,
+                &semantic_result,
+                &rope,
+                Range::default(),
+            )
+            .into_iter()
+            .map(|candidate| candidate.label)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(true), ["let", "return", "arg"]);
+        assert_eq!(labels(false), ["arg"]);
+    }
+}
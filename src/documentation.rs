@@ -0,0 +1,241 @@
+use l_lang::{CompileResult, Function, Span, SymbolId, SymbolKind};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Url};
+
+use crate::syntax::{find_function_by_name_span, find_struct_by_name_span};
+use crate::{offset_to_position, span_text};
+
+/// Builds the Markdown documentation for a symbol: its signature or layout in a code block,
+/// followed by its doc comment and a link to where it is defined.
+pub fn symbol_documentation(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    uri: &Url,
+    symbol_id: SymbolId,
+) -> Option<String> {
+    let span = semantic_result.semantic.get_symbol_span(symbol_id);
+    let defined_at = offset_to_position(span.start as usize, rope);
+    symbol_documentation_defined_at(semantic_result, rope, uri, symbol_id, defined_at)
+}
+
+/// Like [`symbol_documentation`], but links to `defined_at` instead of the position of the
+/// declaration in `rope`, for documentation built from an older text of the document.
+pub fn symbol_documentation_defined_at(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    uri: &Url,
+    symbol_id: SymbolId,
+    defined_at: Option<Position>,
+) -> Option<String> {
+    let semantic = &semantic_result.semantic;
+    let span = semantic.get_symbol_span(symbol_id);
+    let name = span_text(span, rope)?;
+    let signature = match semantic.get_symbol_kind(symbol_id) {
+        SymbolKind::Function => function_signature(find_function_by_name_span(
+            semantic_result.program.file(),
+            span,
+        )?),
+        SymbolKind::Struct => struct_layout(semantic_result, rope, symbol_id)?,
+        SymbolKind::Variable => format!(
+            "let {}: {}",
+            name,
+            semantic
+                .get_symbol_type(symbol_id)?
+                .ty
+                .format_literal_type(semantic)
+        ),
+        SymbolKind::Parameter | SymbolKind::Field => format!(
+            "{}: {}",
+            name,
+            semantic
+                .get_symbol_type(symbol_id)?
+                .ty
+                .format_literal_type(semantic)
+        ),
+    };
+    Some(render(
+        &signature,
+        doc_comment_above(span, rope),
+        uri,
+        defined_at,
+    ))
+}
+
+/// Builds the Markdown documentation for a field of the given struct.
+pub fn field_documentation(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    uri: &Url,
+    struct_id: SymbolId,
+    field_name: &str,
+) -> Option<String> {
+    let semantic = &semantic_result.semantic;
+    let struct_span = semantic.get_symbol_span(struct_id);
+    let struct_name = span_text(struct_span, rope)?;
+    let field = semantic
+        .structs
+        .get(&struct_id)?
+        .fields
+        .iter()
+        .find(|field| field.name == field_name)?;
+    let signature = format!(
+        "{}.{}: {}",
+        struct_name,
+        field.name,
+        field.ty.format_literal_type(semantic)
+    );
+    let field_span = find_struct_by_name_span(semantic_result.program.file(), struct_span)
+        .and_then(|struct_decl| {
+            struct_decl
+                .fields
+                .iter()
+                .filter_map(|field| field.name.as_ref())
+                .find(|name| name.name == field_name)
+        })
+        .map_or(struct_span, |name| name.span);
+    Some(render(
+        &signature,
+        doc_comment_above(field_span, rope),
+        uri,
+        offset_to_position(field_span.start as usize, rope),
+    ))
+}
+
+/// Renders a struct declaration with its fields and their resolved types.
+pub fn struct_layout(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    struct_id: SymbolId,
+) -> Option<String> {
+    let semantic = &semantic_result.semantic;
+    let name = span_text(semantic.get_symbol_span(struct_id), rope)?;
+    let struct_def = semantic.structs.get(&struct_id)?;
+    let mut layout = format!("struct {name} {{\n");
+    for field in struct_def.fields.iter() {
+        layout.push_str(&format!(
+            "    {}: {},\n",
+            field.name,
+            field.ty.format_literal_type(semantic)
+        ));
+    }
+    layout.push('}');
+    Some(layout)
+}
+
+/// Renders the full `fn` signature as written in the declaration.
+pub fn function_signature(function: &Function) -> String {
+    let name = function.name.as_ref().map_or("", |name| name.name.as_str());
+    let params = function
+        .params
+        .iter()
+        .map(|param| {
+            format!(
+                "{}: {}",
+                param.name.as_ref().map_or("_", |name| name.name.as_str()),
+                param.ty.as_ref().map_or("_", |ty| ty.name.as_str())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    match function.ret_ty.as_ref() {
+        Some(ret_ty) => format!("fn {}({}) -> {}", name, params, ret_ty.name),
+        None => format!("fn {name}({params})"),
+    }
+}
+
+/// Collects the run of `//` comment lines directly above the line containing `span`.
+pub fn doc_comment_above(span: Span, rope: &Rope) -> Option<String> {
+    let decl_line = offset_to_position(span.start as usize, rope)?.line as usize;
+    let mut lines = vec![];
+    for line_idx in (0..decl_line).rev() {
+        let line = rope.line(line_idx).to_string();
+        let Some(comment) = line.trim().strip_prefix("//") else {
+            break;
+        };
+        let comment = comment.strip_prefix('/').unwrap_or(comment);
+        lines.push(comment.strip_prefix(' ').unwrap_or(comment).to_string());
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+fn render(signature: &str, doc: Option<String>, uri: &Url, defined_at: Option<Position>) -> String {
+    let mut value = format!("```l\n{signature}\n```");
+    if let Some(doc) = doc {
+        value.push_str("\n\n---\n\n");
+        value.push_str(&doc);
+    }
+    if let Some(position) = defined_at {
+        let file_name = uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_else(|| uri.as_str());
+        value.push_str(&format!(
+            "\n\n---\n\nDefined at [{}:{}:{}]({}#L{})",
+            file_name,
+            position.line + 1,
+            position.character + 1,
+            uri,
+            position.line + 1
+        ));
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use l_lang::compile;
+
+    const SOURCE: &str = "// Unrelated.\n\n/// A point.\n// In two lines.\nstruct Point {\n    // Across.\n    x: int,\n    y: int,\n}\n\nfn origin(scale: int) -> Point {\n    return Point { x: 0, y: scale };\n}\n";
+
+    fn documentation_of(name: &str) -> Option<String> {
+        let semantic_result = compile(SOURCE);
+        let rope = Rope::from_str(SOURCE);
+        let uri = Url::parse("file:///dir/main.l").unwrap();
+        let symbol_id = semantic_result.semantic.get_symbol_at(SOURCE.find(name)?)?;
+        symbol_documentation(&semantic_result, &rope, &uri, symbol_id)
+    }
+
+    #[test]
+    fn documents_structs_with_their_layout_and_comment() {
+        assert_eq!(
+            documentation_of("Point {\n").unwrap(),
+            "```l\nstruct Point {\n    x: int,\n    y: int,\n}\n```\n\n---\n\n\
+             A point.\nIn two lines.\n\n---\n\n\
+             Defined at [main.l:5:8](file:///dir/main.l#L5)"
+        );
+    }
+
+    #[test]
+    fn documents_functions_and_parameters_with_their_signature() {
+        assert_eq!(
+            documentation_of("origin").unwrap(),
+            "```l\nfn origin(scale: int) -> Point\n```\n\n---\n\n\
+             Defined at [main.l:11:4](file:///dir/main.l#L11)"
+        );
+        assert!(documentation_of("scale: int")
+            .unwrap()
+            .starts_with("```l\nscale: int\n```"));
+    }
+
+    #[test]
+    fn documents_fields_with_the_comment_above_them() {
+        let semantic_result = compile(SOURCE);
+        let rope = Rope::from_str(SOURCE);
+        let uri = Url::parse("file:///dir/main.l").unwrap();
+        let struct_id = semantic_result
+            .semantic
+            .get_symbol_at(SOURCE.find("Point {\n").unwrap())
+            .unwrap();
+        assert_eq!(
+            field_documentation(&semantic_result, &rope, &uri, struct_id, "x").unwrap(),
+            "```l\nPoint.x: int\n```\n\n---\n\nAcross.\n\n---\n\n\
+             Defined at [main.l:7:5](file:///dir/main.l#L7)"
+        );
+        assert!(field_documentation(&semantic_result, &rope, &uri, struct_id, "z").is_none());
+    }
+}
@@ -0,0 +1,174 @@
+use l_lang::{Expr, Span};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::syntax::{walk, SyntaxNode};
+use crate::{offset_to_position, Backend};
+
+impl Backend {
+    pub(crate) fn get_folding_ranges(&self, uri: &str) -> Option<Vec<FoldingRange>> {
+        let semantic_result = self.semanticast_map.get(uri)?;
+        let rope = self.document_map.get(uri)?;
+        let line_folding_only = self
+            .client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.text_document.as_ref())
+            .and_then(|text_document| text_document.folding_range.as_ref())
+            .and_then(|folding_range| folding_range.line_folding_only)
+            .unwrap_or(false);
+
+        let mut ranges = vec![];
+        walk(
+            SyntaxNode::File(semantic_result.program.file()),
+            &mut |node| {
+                let span = match node {
+                    // Function bodies and nested blocks
+                    SyntaxNode::Block(block) => block.span,
+                    SyntaxNode::Struct(struct_decl) => struct_decl.span,
+                    SyntaxNode::Expr(Expr::Struct(struct_expr)) => struct_expr.span,
+                    _ => return,
+                };
+                ranges.extend(bracketed_range(span, &rope, line_folding_only));
+            },
+        );
+        ranges.extend(comment_ranges(&rope));
+        ranges.sort_by_key(|range| (range.start_line, range.end_line));
+        Some(ranges)
+    }
+}
+
+/// Folds a `{ ... }` region. Clients that fold whole lines keep the closing brace visible.
+fn bracketed_range(span: Span, rope: &Rope, line_folding_only: bool) -> Option<FoldingRange> {
+    let start = offset_to_position(span.start as usize, rope)?;
+    let end = offset_to_position(span.end as usize, rope)?;
+    if line_folding_only {
+        let end_line = end.line.checked_sub(1)?;
+        if end_line <= start.line {
+            return None;
+        }
+        return Some(FoldingRange {
+            start_line: start.line,
+            end_line,
+            ..Default::default()
+        });
+    }
+    if end.line <= start.line {
+        return None;
+    }
+    Some(FoldingRange {
+        start_line: start.line,
+        start_character: Some(start.character),
+        end_line: end.line,
+        end_character: Some(end.character),
+        ..Default::default()
+    })
+}
+
+/// Folds runs of two or more consecutive `//` comment lines.
+fn comment_ranges(rope: &Rope) -> Vec<FoldingRange> {
+    let mut ranges = vec![];
+    let mut run_start: Option<usize> = None;
+    for line_idx in 0..=rope.len_lines() {
+        let is_comment = line_idx < rope.len_lines()
+            && rope
+                .line(line_idx)
+                .to_string()
+                .trim_start()
+                .starts_with("//");
+        match (is_comment, run_start) {
+            (true, None) => run_start = Some(line_idx),
+            (false, Some(start)) => {
+                if line_idx - 1 > start {
+                    ranges.push(FoldingRange {
+                        start_line: start as u32,
+                        end_line: (line_idx - 1) as u32,
+                        kind: Some(FoldingRangeKind::Comment),
+                        ..Default::default()
+                    });
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, URI};
+
+    const SOURCE: &str = "// One line.\nstruct Point {\n    x: int,\n}\n\n\
+                          // Two\n  // lines.\nfn f() -> Point {\n    {\n        // Inner.\n    }\n    \
+                          return Point {\n        x: 1,\n    };\n}\n// Trailing\n// run.";
+
+    /// The folds of [`SOURCE`] as `(start_line, end_line, is_comment)`.
+    fn folds(line_folding_only: Option<bool>) -> Vec<(u32, u32, bool)> {
+        let (service, _socket) = backend(&[(URI, SOURCE)]);
+        let backend = service.inner();
+        if let Some(line_folding_only) = line_folding_only {
+            let capabilities = ClientCapabilities {
+                text_document: Some(TextDocumentClientCapabilities {
+                    folding_range: Some(FoldingRangeClientCapabilities {
+                        line_folding_only: Some(line_folding_only),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            backend.client_capabilities.set(capabilities).unwrap();
+        }
+        backend
+            .get_folding_ranges(URI)
+            .unwrap()
+            .into_iter()
+            .map(|range| {
+                let is_comment = range.kind == Some(FoldingRangeKind::Comment);
+                (range.start_line, range.end_line, is_comment)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn folds_braces_and_runs_of_comment_lines() {
+        assert_eq!(
+            folds(None),
+            [
+                (1, 3, false),
+                (5, 6, true),
+                (7, 14, false),
+                (8, 10, false),
+                (11, 13, false),
+                (15, 16, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_closing_braces_visible_when_folding_whole_lines() {
+        assert_eq!(
+            folds(Some(true)),
+            [
+                (1, 2, false),
+                (5, 6, true),
+                (7, 13, false),
+                (8, 9, false),
+                (11, 12, false),
+                (15, 16, true),
+            ]
+        );
+        assert_eq!(folds(Some(false)), folds(None));
+    }
+
+    #[test]
+    fn folds_to_the_closing_brace_character() {
+        let rope = Rope::from_str("fn f() {\n    g();\n}\n");
+        let range = bracketed_range(Span { start: 7, end: 19 }, &rope, false).unwrap();
+        assert_eq!(
+            (range.start_character, range.end_character),
+            (Some(7), Some(1))
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use l_lang::{find_node_at_offset, AstNode, CompileResult, Expr, Item, SymbolId, SymbolKind, Type};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::syntax::{find_struct_by_name_span, walk, SyntaxNode};
+use crate::types::infer_expr_type;
+use crate::{position_to_offset, span_text, span_to_range, Backend};
+
+impl Backend {
+    /// Highlights every occurrence of the symbol under the cursor. l-lang bindings are immutable,
+    /// so declarations and struct literal initialisers are the only writes.
+    pub(crate) fn get_document_highlights(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Option<Vec<DocumentHighlight>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let position = params.text_document_position_params.position;
+        let rope = self.document_map.get(&uri)?;
+        let semantic_result = self.semanticast_map.get(&uri)?;
+        let offset = position_to_offset(position, &rope)?;
+
+        // Field access under the cursor, e.g. the `x` of `p.x`
+        if let Some(AstNode::ExprField(field_expr)) =
+            find_node_at_offset(semantic_result.program.file(), offset as u32)
+        {
+            if let Some(field) = field_expr.field.as_ref() {
+                if field.span.start as usize <= offset && offset <= field.span.end as usize {
+                    let struct_id =
+                        self.get_struct_id_from_field(field_expr, &semantic_result, &rope)?;
+                    return field_highlights(&semantic_result, &rope, struct_id, &field.name);
+                }
+            }
+        }
+
+        let symbol_id = semantic_result.semantic.get_symbol_at(offset)?;
+        let symbol_span = semantic_result.semantic.get_symbol_span(symbol_id);
+        if semantic_result.semantic.get_symbol_kind(symbol_id) == SymbolKind::Field {
+            let struct_id = owning_struct(&semantic_result, symbol_id)?;
+            let field_name = span_text(symbol_span, &rope)?;
+            return field_highlights(&semantic_result, &rope, struct_id, &field_name);
+        }
+
+        let mut highlights = vec![DocumentHighlight {
+            range: span_to_range(symbol_span, &rope)?,
+            kind: Some(DocumentHighlightKind::WRITE),
+        }];
+        let ref_ids = semantic_result.semantic.get_symbol_references(symbol_id);
+        highlights.extend(ref_ids.iter().filter_map(|ref_id| {
+            let span = semantic_result.semantic.reference_spans[*ref_id];
+            Some(DocumentHighlight {
+                range: span_to_range(span, &rope)?,
+                kind: Some(DocumentHighlightKind::READ),
+            })
+        }));
+        Some(highlights)
+    }
+}
+
+/// The struct whose declaration contains the given field symbol.
+fn owning_struct(semantic_result: &CompileResult, field_id: SymbolId) -> Option<SymbolId> {
+    let field_span = semantic_result.semantic.get_symbol_span(field_id);
+    semantic_result
+        .program
+        .file()
+        .items
+        .iter()
+        .find_map(|item| match item {
+            Item::Struct(struct_decl)
+                if struct_decl.span.start <= field_span.start
+                    && field_span.end <= struct_decl.span.end =>
+            {
+                let name = struct_decl.name.as_ref()?;
+                semantic_result
+                    .semantic
+                    .get_symbol_at(name.span.start as usize)
+            }
+            _ => None,
+        })
+}
+
+/// The field's declaration and literal initialisers as writes, and its accesses as reads.
+fn field_highlights(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    struct_id: SymbolId,
+    field_name: &str,
+) -> Option<Vec<DocumentHighlight>> {
+    let file = semantic_result.program.file();
+    let mut highlights = vec![];
+
+    let struct_span = semantic_result.semantic.get_symbol_span(struct_id);
+    if let Some(struct_decl) = find_struct_by_name_span(file, struct_span) {
+        highlights.extend(
+            struct_decl
+                .fields
+                .iter()
+                .filter_map(|field| field.name.as_ref())
+                .filter(|name| name.name == field_name)
+                .filter_map(|name| {
+                    Some(DocumentHighlight {
+                        range: span_to_range(name.span, rope)?,
+                        kind: Some(DocumentHighlightKind::WRITE),
+                    })
+                }),
+        );
+    }
+
+    walk(SyntaxNode::File(file), &mut |node| match node {
+        SyntaxNode::Expr(Expr::Field(field_expr)) => {
+            let Some(field) = field_expr.field.as_ref() else {
+                return;
+            };
+            let receiver_type = field_expr
+                .object
+                .as_deref()
+                .and_then(|object| infer_expr_type(object, semantic_result, rope));
+            if field.name == field_name && receiver_type == Some(Type::Struct(struct_id)) {
+                if let Some(range) = span_to_range(field.span, rope) {
+                    highlights.push(DocumentHighlight {
+                        range,
+                        kind: Some(DocumentHighlightKind::READ),
+                    });
+                }
+            }
+        }
+        SyntaxNode::Expr(Expr::Struct(struct_expr)) => {
+            let literal_struct_id = struct_expr.name.as_ref().and_then(|name| {
+                let reference_id = semantic_result
+                    .semantic
+                    .get_reference_at(name.span.start as usize)?;
+                semantic_result.semantic.references[reference_id]
+            });
+            if literal_struct_id != Some(struct_id) {
+                return;
+            }
+            for name in struct_expr
+                .fields
+                .iter()
+                .filter_map(|field| field.name.as_ref())
+                .filter(|name| name.name == field_name)
+            {
+                if let Some(range) = span_to_range(name.span, rope) {
+                    highlights.push(DocumentHighlight {
+                        range,
+                        kind: Some(DocumentHighlightKind::WRITE),
+                    });
+                }
+            }
+        }
+        _ => {}
+    });
+    Some(highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, Fixture};
+
+    /// The highlighted text at the cursor in `marked`, as `(line, character, kind)` in order.
+    fn highlights(marked: &str) -> Vec<(u32, u32, DocumentHighlightKind)> {
+        let fixture = Fixture::new(marked);
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        let mut highlights = service
+            .inner()
+            .get_document_highlights(DocumentHighlightParams {
+                text_document_position_params: fixture.position_params(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .map(|highlight| {
+                let start = highlight.range.start;
+                (start.line, start.character, highlight.kind.unwrap())
+            })
+            .collect::<Vec<_>>();
+        highlights.sort_by_key(|(line, character, _)| (*line, *character));
+        highlights
+    }
+
+    const READ: DocumentHighlightKind = DocumentHighlightKind::READ;
+    const WRITE: DocumentHighlightKind = DocumentHighlightKind::WRITE;
+
+    #[test]
+    fn marks_the_declaration_as_the_only_write() {
+        assert_eq!(
+            highlights("fn f(a: int) -> int {\n    let b = $0a + 1;\n    return a * b;\n}\n"),
+            [(0, 5, WRITE), (1, 12, READ), (2, 11, READ)]
+        );
+    }
+
+    const POINT: &str = "struct Point {\n    x: int,\n    y: int,\n}\n\n\
+                         fn make() -> Point {\n    return Point { x: 1, y: 2 };\n}\n\n";
+
+    #[test]
+    fn highlights_fields_from_an_access() {
+        assert_eq!(
+            highlights(&format!(
+                "{POINT}fn f(p: Point) -> int {{\n    return p.$0x + make().x + p.y;\n}}\n"
+            )),
+            [
+                (1, 4, WRITE),
+                (6, 19, WRITE),
+                (10, 13, READ),
+                (10, 24, READ)
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_fields_from_the_declaration() {
+        assert_eq!(
+            highlights(
+                "struct Point {\n    $0x: int,\n}\n\n\
+                 fn f(p: Point) -> Point {\n    return Point { x: p.x };\n}\n"
+            ),
+            [(1, 4, WRITE), (5, 19, WRITE), (5, 24, READ)]
+        );
+    }
+}
@@ -0,0 +1,580 @@
+use l_lang::{CompileResult, Expr, Function, Item, Span, Stmt, SymbolKind, Type};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use crate::documentation::struct_layout;
+use crate::syntax::{find_function_by_name_span, walk, walk_overlapping, SyntaxNode};
+use crate::types::{function_return_type, resolve_callee};
+use crate::{offset_to_position, span_text, Backend};
+
+/// Carried in `InlayHint::data` so that `inlayHint/resolve` can find the hinted symbol again.
+#[derive(Debug, Serialize, Deserialize)]
+struct InlayHintData {
+    uri: String,
+    /// Start offset of the variable's or function's name.
+    symbol_offset: u32,
+}
+
+pub fn hint_data(uri: &str, symbol_offset: u32) -> Option<Value> {
+    serde_json::to_value(InlayHintData {
+        uri: uri.to_string(),
+        symbol_offset,
+    })
+    .ok()
+}
+
+impl Backend {
+    /// Fills in the edit that writes a type hint into the source, and shows the definition of
+    /// the hinted type when it is a struct.
+    pub(crate) fn resolve_inlay_hint(&self, mut hint: InlayHint) -> InlayHint {
+        let Some(data) = hint
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value::<InlayHintData>(data).ok())
+        else {
+            return hint;
+        };
+        let Some(semantic_result) = self.semanticast_map.get(&data.uri) else {
+            return hint;
+        };
+        let Some(rope) = self.document_map.get(&data.uri) else {
+            return hint;
+        };
+        let Some((ty, prefix)) = hinted_type(&semantic_result, &rope, data.symbol_offset) else {
+            return hint;
+        };
+        if !is_annotated(&semantic_result, data.symbol_offset) {
+            hint.text_edits = Some(vec![TextEdit::new(
+                Range::new(hint.position, hint.position),
+                format!(
+                    "{prefix}{}",
+                    ty.format_literal_type(&semantic_result.semantic)
+                ),
+            )]);
+        }
+        if let Type::Struct(struct_id) = ty {
+            if let Some(layout) = struct_layout(&semantic_result, &rope, struct_id) {
+                hint.tooltip = Some(InlayHintTooltip::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("```l\n{layout}\n```"),
+                }));
+            }
+        }
+        hint
+    }
+}
+
+/// The type a variable or return type hint shows, with the text that introduces it when written
+/// out in the source.
+fn hinted_type(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    symbol_offset: u32,
+) -> Option<(Type, &'static str)> {
+    let semantic = &semantic_result.semantic;
+    let symbol_id = semantic.get_symbol_at(symbol_offset as usize)?;
+    match semantic.get_symbol_kind(symbol_id) {
+        SymbolKind::Variable => Some((semantic.get_symbol_type(symbol_id)?.ty.clone(), ": ")),
+        SymbolKind::Function => {
+            let function = find_function_by_name_span(
+                semantic_result.program.file(),
+                semantic.get_symbol_span(symbol_id),
+            )?;
+            Some((
+                function_return_type(function, semantic_result, rope)?,
+                " -> ",
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Whether the `let` or function named at `symbol_offset` already has a type annotation.
+fn is_annotated(semantic_result: &CompileResult, symbol_offset: u32) -> bool {
+    let mut annotated = false;
+    walk(
+        SyntaxNode::File(semantic_result.program.file()),
+        &mut |node| match node {
+            SyntaxNode::Stmt(Stmt::Let(let_stmt))
+                if let_stmt
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.span.start == symbol_offset) =>
+            {
+                annotated = let_stmt.ty.is_some();
+            }
+            SyntaxNode::Function(function)
+                if function
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.span.start == symbol_offset) =>
+            {
+                annotated = function.ret_ty.is_some();
+            }
+            _ => {}
+        },
+    );
+    annotated
+}
+
+/// Whether `offset` is inside `visible`, the part of the document hints are requested for.
+pub fn is_visible(visible: Span, offset: u32) -> bool {
+    visible.start <= offset && offset <= visible.end
+}
+
+/// A `name:` hint before every call argument in `visible`, unless the argument is a variable with
+/// the same name as the parameter.
+pub fn parameter_hints(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    visible: Span,
+) -> Vec<InlayHint> {
+    let mut hints = vec![];
+    walk_overlapping(
+        SyntaxNode::File(semantic_result.program.file()),
+        visible,
+        &mut |node| {
+            let SyntaxNode::Expr(Expr::Call(call_expr)) = node else {
+                return;
+            };
+            let Some(function) = call_expr
+                .callee
+                .as_deref()
+                .and_then(|callee| resolve_callee(callee, semantic_result))
+            else {
+                return;
+            };
+            for (arg, param) in call_expr.args.iter().zip(function.params.iter()) {
+                let Some(param_name) = param.name.as_ref() else {
+                    continue;
+                };
+                if matches!(arg, Expr::Name(name_expr) if name_expr.name == param_name.name)
+                    || !is_visible(visible, arg.span().start)
+                {
+                    continue;
+                }
+                let Some(position) = offset_to_position(arg.span().start as usize, rope) else {
+                    continue;
+                };
+                hints.push(InlayHint {
+                    position,
+                    label: InlayHintLabel::String(format!("{}:", param_name.name)),
+                    kind: Some(InlayHintKind::PARAMETER),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: Some(false),
+                    padding_right: Some(true),
+                    data: None,
+                });
+            }
+        },
+    );
+    hints
+}
+
+/// Which optional inlay hints to show, read from the `inlayHints` section of the server
+/// configuration.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct InlayHintsConfig {
+    /// `: Type` after `let` bindings.
+    pub variable_types: bool,
+    /// `name:` before call arguments.
+    pub parameter_names: bool,
+    /// `-> Type` after the parameter list of functions without a return annotation.
+    pub return_types: bool,
+    /// `// fn name` after the closing brace of long functions.
+    pub closing_brace_hints: bool,
+    /// `// Name` after the closing brace of long struct literals.
+    pub struct_literal_hints: bool,
+    /// How many lines a function or struct literal must span to get a closing brace hint.
+    pub closing_brace_min_lines: u32,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        Self {
+            variable_types: true,
+            parameter_names: true,
+            return_types: true,
+            closing_brace_hints: true,
+            struct_literal_hints: true,
+            closing_brace_min_lines: 25,
+        }
+    }
+}
+
+impl InlayHintsConfig {
+    /// Reads the config from initialization options or `workspace/didChangeConfiguration`
+    /// settings, which may or may not be nested under the `l-language-server` section.
+    pub fn from_settings(settings: &Value) -> Option<Self> {
+        let section = settings.get("l-language-server").unwrap_or(settings);
+        serde_json::from_value(section.get("inlayHints")?.clone()).ok()
+    }
+}
+
+/// A `-> Type` hint after the parameter list of every function in `visible` whose return type is
+/// inferred.
+pub fn return_type_hints(
+    uri: &str,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    visible: Span,
+) -> Vec<InlayHint> {
+    semantic_result
+        .program
+        .file()
+        .items
+        .iter()
+        .filter_map(|item| {
+            let Item::Function(function) = item else {
+                return None;
+            };
+            if function.ret_ty.is_some() {
+                return None;
+            }
+            let offset = params_end(function, rope)?;
+            if !is_visible(visible, offset as u32) {
+                return None;
+            }
+            let name = function.name.as_ref()?;
+            let ty = function_return_type(function, semantic_result, rope)?;
+            let position = offset_to_position(offset, rope)?;
+            Some(InlayHint {
+                position,
+                label: InlayHintLabel::String(format!(
+                    "-> {}",
+                    ty.format_literal_type(&semantic_result.semantic)
+                )),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: Some(false),
+                data: hint_data(uri, name.span.start),
+            })
+        })
+        .collect()
+}
+
+/// `// fn name` after functions and `// Name` after struct literals that span at least
+/// `config.closing_brace_min_lines` lines and end in `visible`, for whichever of the two are
+/// enabled.
+pub fn closing_brace_hints(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    config: &InlayHintsConfig,
+    visible: Span,
+) -> Vec<InlayHint> {
+    let mut hints = vec![];
+    walk_overlapping(
+        SyntaxNode::File(semantic_result.program.file()),
+        visible,
+        &mut |node| {
+            let (span, label) = match node {
+                SyntaxNode::Function(function) if config.closing_brace_hints => {
+                    let Some(name) = function.name.as_ref() else {
+                        return;
+                    };
+                    (function.span, format!("// fn {}", name.name))
+                }
+                SyntaxNode::Expr(Expr::Struct(struct_expr)) if config.struct_literal_hints => {
+                    let Some(name) = struct_expr.name.as_ref() else {
+                        return;
+                    };
+                    (struct_expr.span, format!("// {}", name.name))
+                }
+                _ => return,
+            };
+            if !is_visible(visible, span.end) {
+                return;
+            }
+            let (Some(start), Some(end)) = (
+                offset_to_position(span.start as usize, rope),
+                offset_to_position(span.end as usize, rope),
+            ) else {
+                return;
+            };
+            if end.line - start.line + 1 < config.closing_brace_min_lines {
+                return;
+            }
+            hints.push(InlayHint {
+                position: end,
+                label: InlayHintLabel::String(label),
+                kind: None,
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: Some(false),
+                data: None,
+            });
+        },
+    );
+    hints
+}
+
+/// The byte offset just past the `)` closing a function's parameter list.
+pub fn params_end(function: &Function, rope: &Rope) -> Option<usize> {
+    let search_from = function
+        .params
+        .last()
+        .map(|param| param.span.end)
+        .or(function.name.as_ref().map(|name| name.span.end))?;
+    let body_start = function
+        .body
+        .as_ref()
+        .map_or(function.span.end, |body| body.span.start);
+    let text = span_text(
+        Span {
+            start: search_from,
+            end: body_start,
+        },
+        rope,
+    )?;
+    let paren = text.find(')')?;
+    Some(search_from as usize + paren + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, URI};
+    use l_lang::compile;
+
+    /// Where each hint goes and what it reads, in order.
+    fn rendered(hints: Vec<InlayHint>) -> Vec<(u32, u32, String)> {
+        hints
+            .into_iter()
+            .map(|hint| {
+                let InlayHintLabel::String(label) = hint.label else {
+                    panic!("label parts in {hint:?}");
+                };
+                (hint.position.line, hint.position.character, label)
+            })
+            .collect()
+    }
+
+    /// All of `source`, as the visible span.
+    fn everything(source: &str) -> Span {
+        Span {
+            start: 0,
+            end: source.len() as u32,
+        }
+    }
+
+    const CALLS: &str = "fn area(width: int, height: int) -> int {\n    return width * height;\n}\n\n\
+                         fn f(height: int) -> int {\n    return area(2, height) + area(height + 1, 3);\n}\n";
+
+    #[test]
+    fn names_arguments_unless_they_repeat_the_parameter() {
+        let hints = parameter_hints(&compile(CALLS), &Rope::from_str(CALLS), everything(CALLS));
+        assert_eq!(
+            rendered(hints),
+            [
+                (5, 16, "width:".to_string()),
+                (5, 34, "width:".to_string()),
+                (5, 46, "height:".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_calls_that_do_not_resolve() {
+        let source = "fn f() -> int {\n    return g(1);\n}\n";
+        let hints = parameter_hints(
+            &compile(source),
+            &Rope::from_str(source),
+            everything(source),
+        );
+        assert!(hints.is_empty());
+    }
+
+    #[test]
+    fn reads_the_config_with_or_without_the_server_section() {
+        let flat = serde_json::json!({ "inlayHints": { "returnTypes": false } });
+        let config = InlayHintsConfig::from_settings(&flat).unwrap();
+        assert!(!config.return_types);
+        assert!(config.variable_types);
+        assert_eq!(config.closing_brace_min_lines, 25);
+
+        let nested = serde_json::json!({
+            "l-language-server": { "inlayHints": { "closingBraceMinLines": 3 } }
+        });
+        let config = InlayHintsConfig::from_settings(&nested).unwrap();
+        assert_eq!(config.closing_brace_min_lines, 3);
+        assert!(config.return_types);
+
+        assert!(InlayHintsConfig::from_settings(&serde_json::json!({})).is_none());
+    }
+
+    #[test]
+    fn hints_inferred_return_types_after_the_parameters() {
+        let source =
+            "fn one() {\n    return 1;\n}\n\nfn two(a: int) -> int {\n    return 2;\n}\n\n\
+                      fn nothing(a: int) {\n}\n";
+        let hints = return_type_hints(
+            URI,
+            &compile(source),
+            &Rope::from_str(source),
+            everything(source),
+        );
+        assert_eq!(rendered(hints), [(0, 8, "-> int".to_string())]);
+    }
+
+    const LONG: &str = "struct P {\n    x: int,\n}\n\nfn f() -> P {\n    let a = 1;\n    \
+                        return P {\n        x: a,\n    };\n}\n";
+
+    fn closing_brace_labels(config: InlayHintsConfig) -> Vec<(u32, u32, String)> {
+        let hints = closing_brace_hints(
+            &compile(LONG),
+            &Rope::from_str(LONG),
+            &config,
+            everything(LONG),
+        );
+        rendered(hints)
+    }
+
+    #[test]
+    fn hints_the_closing_braces_of_long_functions_and_literals() {
+        let config = InlayHintsConfig {
+            closing_brace_min_lines: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            closing_brace_labels(config),
+            [(9, 1, "// fn f".to_string()), (8, 5, "// P".to_string())]
+        );
+        let config = InlayHintsConfig {
+            closing_brace_min_lines: 4,
+            ..config
+        };
+        assert_eq!(
+            closing_brace_labels(config),
+            [(9, 1, "// fn f".to_string())]
+        );
+        let config = InlayHintsConfig {
+            closing_brace_hints: false,
+            closing_brace_min_lines: 3,
+            ..config
+        };
+        assert_eq!(closing_brace_labels(config), [(8, 5, "// P".to_string())]);
+        assert!(closing_brace_labels(InlayHintsConfig::default()).is_empty());
+    }
+
+    /// The span of `source` from the start of line `first` to the end of line `last`.
+    fn lines(source: &str, first: usize, last: usize) -> Span {
+        let rope = Rope::from_str(source);
+        Span {
+            start: rope.line_to_byte(first) as u32,
+            end: (rope.line_to_byte(last + 1) - 1) as u32,
+        }
+    }
+
+    #[test]
+    fn only_hints_the_visible_lines() {
+        let source =
+            format!("{CALLS}\nfn g() {{\n    return area(\n        1,\n        2\n    );\n}}\n");
+        let semantic_result = compile(&source);
+        let rope = Rope::from_str(&source);
+        let hints = |visible| rendered(parameter_hints(&semantic_result, &rope, visible));
+        assert_eq!(hints(lines(&source, 0, 4)), []);
+        assert_eq!(hints(lines(&source, 5, 5)).len(), 3);
+        // Only the arguments of a call that starts above the range are hinted.
+        assert_eq!(
+            hints(lines(&source, 10, 11)),
+            [
+                (10, 8, "width:".to_string()),
+                (11, 8, "height:".to_string())
+            ]
+        );
+        assert_eq!(
+            hints(lines(&source, 11, 12)),
+            [(11, 8, "height:".to_string())]
+        );
+
+        let return_hints =
+            |visible| rendered(return_type_hints(URI, &semantic_result, &rope, visible));
+        assert_eq!(return_hints(lines(&source, 0, 7)), []);
+        assert_eq!(
+            return_hints(lines(&source, 8, 8)),
+            [(8, 6, "-> int".to_string())]
+        );
+    }
+
+    #[test]
+    fn hints_closing_braces_that_end_in_the_range() {
+        let config = InlayHintsConfig {
+            closing_brace_min_lines: 3,
+            ..Default::default()
+        };
+        let semantic_result = compile(LONG);
+        let rope = Rope::from_str(LONG);
+        let hints = |visible| {
+            rendered(closing_brace_hints(
+                &semantic_result,
+                &rope,
+                &config,
+                visible,
+            ))
+        };
+        assert_eq!(hints(lines(LONG, 0, 7)), []);
+        assert_eq!(hints(lines(LONG, 8, 8)), [(8, 5, "// P".to_string())]);
+        assert_eq!(hints(lines(LONG, 9, 9)), [(9, 1, "// fn f".to_string())]);
+    }
+
+    const RESOLVE: &str =
+        "struct P {\n    x: int,\n}\n\nfn make() {\n    return P { x: 1 };\n}\n\n\
+                           fn f() {\n    let a = make();\n    let b: int = 1;\n}\n";
+
+    /// Resolves a hint at `position` for the symbol named at the first `name` in [`RESOLVE`].
+    fn resolve(name: &str, position: Position) -> InlayHint {
+        let (service, _socket) = backend(&[(URI, RESOLVE)]);
+        let hint = InlayHint {
+            position,
+            label: InlayHintLabel::String(String::new()),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: hint_data(URI, RESOLVE.find(name).unwrap() as u32),
+        };
+        service.inner().resolve_inlay_hint(hint)
+    }
+
+    #[test]
+    fn resolves_hints_into_insertable_annotations() {
+        let position = Position::new(9, 9);
+        let hint = resolve("a = ", position);
+        assert_eq!(
+            hint.text_edits,
+            Some(vec![TextEdit::new(
+                Range::new(position, position),
+                ": P".to_string()
+            )])
+        );
+        let Some(InlayHintTooltip::MarkupContent(tooltip)) = hint.tooltip else {
+            panic!("no tooltip in {hint:?}");
+        };
+        assert_eq!(tooltip.value, "```l\nstruct P {\n    x: int,\n}\n```");
+
+        let position = Position::new(4, 9);
+        let hint = resolve("make", position);
+        assert_eq!(
+            hint.text_edits,
+            Some(vec![TextEdit::new(
+                Range::new(position, position),
+                " -> P".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn does_not_annotate_bindings_twice() {
+        let hint = resolve("b: int", Position::new(10, 9));
+        assert_eq!(hint.text_edits, None);
+        assert!(hint.tooltip.is_none());
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use l_lang::{BinaryOp, Block, CompileResult, Expr, Function, Literal, Stmt, SymbolId};
+
+use crate::syntax::find_function_by_name_span;
+
+/// How many calls may be active at once before a run is reported as runaway recursion.
+const MAX_CALL_DEPTH: u32 = 64;
+
+/// How many expressions a run may evaluate, which bounds recursion that fans out.
+const MAX_STEPS: u32 = 1_000_000;
+
+/// A value computed by [`run`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    String(String),
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+    /// What a function without a `return` value produces.
+    Unit,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::String(value) => write!(f, "{value:?}"),
+            Value::Struct { name, fields } if fields.is_empty() => write!(f, "{name} {{}}"),
+            Value::Struct { name, fields } => {
+                write!(f, "{name} {{ ")?;
+                for (index, (field, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{field}: {value}")?;
+                }
+                write!(f, " }}")
+            }
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// Calls `function` without arguments and evaluates it to its return value. The program is
+/// expected to compile without errors; whatever the interpreter cannot evaluate is reported as
+/// an error message.
+pub fn run(semantic_result: &CompileResult, function: &Function) -> Result<Value, String> {
+    Interpreter {
+        semantic_result,
+        depth: 0,
+        steps: 0,
+    }
+    .call(function, vec![])
+}
+
+struct Interpreter<'a> {
+    semantic_result: &'a CompileResult,
+    depth: u32,
+    steps: u32,
+}
+
+impl Interpreter<'_> {
+    fn call(&mut self, function: &Function, args: Vec<Value>) -> Result<Value, String> {
+        let name = function
+            .name
+            .as_ref()
+            .map_or("<anonymous>", |name| name.name.as_str());
+        if function.params.len() != args.len() {
+            return Err(format!(
+                "`{name}` takes {} argument(s) but got {}",
+                function.params.len(),
+                args.len()
+            ));
+        }
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(format!(
+                "`{name}` recursed more than {MAX_CALL_DEPTH} calls deep"
+            ));
+        }
+        let semantic = &self.semantic_result.semantic;
+        let mut locals = HashMap::new();
+        for (param, arg) in function.params.iter().zip(args) {
+            let symbol_id = param
+                .name
+                .as_ref()
+                .and_then(|name| semantic.get_symbol_at(name.span.start as usize))
+                .ok_or_else(|| format!("a parameter of `{name}` has no name"))?;
+            locals.insert(symbol_id, arg);
+        }
+        let body = function
+            .body
+            .as_ref()
+            .ok_or_else(|| format!("`{name}` has no body"))?;
+        self.depth += 1;
+        let returned = self.block(body, &mut locals);
+        self.depth -= 1;
+        Ok(returned?.unwrap_or(Value::Unit))
+    }
+
+    /// Runs the statements of `block`, stopping at the first `return`, whose value it returns.
+    fn block(
+        &mut self,
+        block: &Block,
+        locals: &mut HashMap<SymbolId, Value>,
+    ) -> Result<Option<Value>, String> {
+        for stmt in block.stmts.iter() {
+            match stmt {
+                Stmt::Let(let_stmt) => {
+                    let init = let_stmt
+                        .init
+                        .as_deref()
+                        .ok_or("a `let` has no initialiser")?;
+                    let value = self.eval(init, locals)?;
+                    let symbol_id = let_stmt
+                        .name
+                        .as_ref()
+                        .and_then(|name| {
+                            self.semantic_result
+                                .semantic
+                                .get_symbol_at(name.span.start as usize)
+                        })
+                        .ok_or("a `let` has no name")?;
+                    locals.insert(symbol_id, value);
+                }
+                Stmt::Return(return_stmt) => {
+                    let value = match return_stmt.value.as_deref() {
+                        Some(value) => self.eval(value, locals)?,
+                        None => Value::Unit,
+                    };
+                    return Ok(Some(value));
+                }
+                Stmt::Expr(expr_stmt) => {
+                    if let Some(expr) = expr_stmt.expr.as_deref() {
+                        self.eval(expr, locals)?;
+                    }
+                }
+                Stmt::Block(block) => {
+                    if let Some(value) = self.block(block, locals)? {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn eval(&mut self, expr: &Expr, locals: &HashMap<SymbolId, Value>) -> Result<Value, String> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(format!("gave up after evaluating {MAX_STEPS} expressions"));
+        }
+        let semantic = &self.semantic_result.semantic;
+        match expr {
+            Expr::Name(name_expr) => semantic
+                .get_reference_at(name_expr.span.start as usize)
+                .and_then(|reference_id| semantic.references[reference_id])
+                .and_then(|symbol_id| locals.get(&symbol_id))
+                .cloned()
+                .ok_or_else(|| format!("`{}` is not a value", name_expr.name)),
+            Expr::Field(field_expr) => {
+                let object = field_expr
+                    .object
+                    .as_deref()
+                    .ok_or("a field access has no receiver")?;
+                let field = field_expr
+                    .field
+                    .as_ref()
+                    .ok_or("a field access has no field name")?;
+                match self.eval(object, locals)? {
+                    Value::Struct { fields, .. } => fields
+                        .into_iter()
+                        .find(|(name, _)| *name == field.name)
+                        .map(|(_, value)| value)
+                        .ok_or_else(|| format!("no field `{}`", field.name)),
+                    value => Err(format!("cannot read `{}` of {value}", field.name)),
+                }
+            }
+            Expr::Call(call_expr) => {
+                let Some(Expr::Name(callee)) = call_expr.callee.as_deref() else {
+                    return Err("only named functions can be called".to_string());
+                };
+                let function = semantic
+                    .get_reference_at(callee.span.start as usize)
+                    .and_then(|reference_id| semantic.references[reference_id])
+                    .and_then(|symbol_id| {
+                        find_function_by_name_span(
+                            self.semantic_result.program.file(),
+                            semantic.get_symbol_span(symbol_id),
+                        )
+                    })
+                    .ok_or_else(|| format!("`{}` is not a function", callee.name))?;
+                let args = call_expr
+                    .args
+                    .iter()
+                    .map(|arg| self.eval(arg, locals))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(function, args)
+            }
+            Expr::Struct(struct_expr) => {
+                let name = struct_expr
+                    .name
+                    .as_ref()
+                    .ok_or("a struct literal has no name")?;
+                let fields = struct_expr
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let field_name = field.name.as_ref().ok_or("a field has no name")?;
+                        let value = field
+                            .value
+                            .as_deref()
+                            .ok_or_else(|| format!("`{}` has no value", field_name.name))?;
+                        Ok((field_name.name.clone(), self.eval(value, locals)?))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(Value::Struct {
+                    name: name.name.clone(),
+                    fields,
+                })
+            }
+            Expr::Binary(binary_expr) => {
+                let (Some(lhs), Some(rhs)) =
+                    (binary_expr.lhs.as_deref(), binary_expr.rhs.as_deref())
+                else {
+                    return Err("an operator is missing an operand".to_string());
+                };
+                let lhs = self.eval(lhs, locals)?;
+                let rhs = self.eval(rhs, locals)?;
+                binary(binary_expr.op, lhs, rhs)
+            }
+            Expr::Paren(paren_expr) => {
+                let inner = paren_expr.expr.as_deref().ok_or("empty parentheses")?;
+                self.eval(inner, locals)
+            }
+            Expr::Literal(literal) => Ok(match &literal.value {
+                Literal::Int(value) => Value::Int(*value),
+                Literal::Bool(value) => Value::Bool(*value),
+                Literal::String(value) => Value::String(value.clone()),
+            }),
+        }
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, String> {
+    match (op, lhs, rhs) {
+        (BinaryOp::Add, Value::String(lhs), Value::String(rhs)) => Ok(Value::String(lhs + &rhs)),
+        (_, Value::Int(lhs), Value::Int(rhs)) => {
+            let result = match op {
+                BinaryOp::Add => lhs.checked_add(rhs),
+                BinaryOp::Sub => lhs.checked_sub(rhs),
+                BinaryOp::Mul => lhs.checked_mul(rhs),
+                BinaryOp::Div if rhs == 0 => return Err("division by zero".to_string()),
+                BinaryOp::Div => lhs.checked_div(rhs),
+            };
+            result
+                .map(Value::Int)
+                .ok_or_else(|| "integer overflow".to_string())
+        }
+        (op, lhs, rhs) => Err(format!("cannot apply {op:?} to {lhs} and {rhs}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use l_lang::{compile, Item};
+
+    fn run_named(source: &str, name: &str) -> Result<Value, String> {
+        let semantic_result = compile(source);
+        let function = semantic_result
+            .program
+            .file()
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Function(function)
+                    if function.name.as_ref().is_some_and(|n| n.name == name) =>
+                {
+                    Some(function)
+                }
+                _ => None,
+            })
+            .unwrap();
+        run(&semantic_result, function)
+    }
+
+    #[test]
+    fn evaluates_calls_bindings_and_fields() {
+        let source = "struct Point {\n    x: int,\n    y: int,\n}\n\nfn add(a: Point, b: Point) -> Point {\n    return Point { x: a.x + b.x, y: a.y + b.y };\n}\n\nfn main() -> int {\n    let p = add(Point { x: 1, y: 2 }, Point { x: 3, y: 4 });\n    {\n        return p.x * (p.y - 1);\n    }\n}\n";
+        assert_eq!(run_named(source, "main"), Ok(Value::Int(20)));
+    }
+
+    #[test]
+    fn renders_structs_and_unit() {
+        let source = "struct Point {\n    x: int,\n    y: bool,\n}\n\nfn main() -> Point {\n    return Point { x: 1, y: true };\n}\n\nfn test_nothing() {\n    main();\n}\n";
+        assert_eq!(
+            run_named(source, "main").unwrap().to_string(),
+            "Point { x: 1, y: true }"
+        );
+        assert_eq!(run_named(source, "test_nothing"), Ok(Value::Unit));
+    }
+
+    #[test]
+    fn reports_runtime_errors() {
+        assert_eq!(
+            run_named("fn main() -> int {\n    return 1 / (2 - 2);\n}\n", "main"),
+            Err("division by zero".to_string())
+        );
+        assert_eq!(
+            run_named("fn main() -> int {\n    return main();\n}\n", "main"),
+            Err(format!(
+                "`main` recursed more than {MAX_CALL_DEPTH} calls deep"
+            ))
+        );
+        assert_eq!(
+            run_named("fn main(a: int) -> int {\n    return a;\n}\n", "main"),
+            Err("`main` takes 1 argument(s) but got 0".to_string())
+        );
+    }
+
+    #[test]
+    fn bounds_recursion_that_fans_out() {
+        let source = "fn f(a: int) -> int {\n    return f(a) + f(a);\n}\n\nfn main() -> int {\n    return f(1);\n}\n";
+        assert!(run_named(source, "main").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use l_lang::{CompileResult, Span, SymbolId, SymbolKind, Type};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range, Url};
+
+use crate::documentation::symbol_documentation_defined_at;
+use crate::syntax::{ancestors_at, SyntaxNode};
+use crate::{offset_to_position, position_to_offset, span_text, symbol_at_offset, Backend};
+
+/// The most recent analysis of a document that parsed without errors, kept while the user is in
+/// the middle of an edit that leaves the current text unparseable.
+#[derive(Debug)]
+pub struct LastGoodAnalysis {
+    pub result: CompileResult,
+    /// The text `result` was computed from.
+    pub rope: Rope,
+    /// How the text changed since then.
+    pub edit: EditMap,
+}
+
+impl LastGoodAnalysis {
+    pub fn new(result: CompileResult, rope: Rope) -> Self {
+        Self {
+            result,
+            rope,
+            edit: EditMap::default(),
+        }
+    }
+
+    /// Records the latest text of the document so that offsets can be mapped between the two.
+    pub fn update(&mut self, current: &Rope) {
+        self.edit = EditMap::between(&self.rope, current);
+    }
+
+    /// Maps a span of the analysed text into the current text, failing if it was edited.
+    pub fn span_to_current(&self, span: Span) -> Option<(usize, usize)> {
+        let start = self.edit.map_to_new(span.start as usize)?;
+        let end = self.edit.map_to_new(span.end as usize)?;
+        Some((start, end))
+    }
+
+    /// Resolves the struct type of an `a.b.c` receiver chain, using the binding named `a` that is
+    /// visible at `offset` of the current text.
+    pub fn struct_for_chain(&self, offset: usize, chain: &[String]) -> Option<SymbolId> {
+        let semantic = &self.result.semantic;
+        let old_offset = self.edit.map_to_old_clamped(offset);
+
+        // Only bindings of the enclosing function are in scope.
+        let scope = ancestors_at(self.result.program.file(), old_offset as u32)
+            .into_iter()
+            .find_map(|node| match node {
+                SyntaxNode::Function(function) => Some(function.span),
+                _ => None,
+            });
+        let (first, rest) = chain.split_first()?;
+        let symbol_id = semantic
+            .symbol_spans
+            .iter_enumerated()
+            .filter(|(symbol_id, span)| {
+                matches!(
+                    semantic.get_symbol_kind(*symbol_id),
+                    SymbolKind::Variable | SymbolKind::Parameter
+                ) && (span.start as usize) <= old_offset
+                    && scope.is_none_or(|scope| scope.start <= span.start && span.end <= scope.end)
+                    && span_text(**span, &self.rope).as_deref() == Some(first.as_str())
+            })
+            .max_by_key(|(_, span)| span.start)
+            .map(|(symbol_id, _)| symbol_id)?;
+
+        let Type::Struct(mut struct_id) = semantic.get_symbol_type(symbol_id)?.ty.clone() else {
+            return None;
+        };
+        for field_name in rest {
+            let field = semantic
+                .structs
+                .get(&struct_id)?
+                .fields
+                .iter()
+                .find(|field| &field.name == field_name)?;
+            let Type::Struct(next_struct_id) = field.ty.clone() else {
+                return None;
+            };
+            struct_id = next_struct_id;
+        }
+        Some(struct_id)
+    }
+}
+
+/// The single region that differs between the analysed text and the current one, found by
+/// trimming their common prefix and suffix. Offsets are in bytes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EditMap {
+    start: usize,
+    old_end: usize,
+    new_end: usize,
+}
+
+impl EditMap {
+    pub fn between(old: &Rope, new: &Rope) -> Self {
+        let old_text = old.to_string();
+        let new_text = new.to_string();
+        let (old_bytes, new_bytes) = (old_text.as_bytes(), new_text.as_bytes());
+        let prefix = old_bytes
+            .iter()
+            .zip(new_bytes.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let max_suffix = old_bytes.len().min(new_bytes.len()) - prefix;
+        let suffix = old_bytes
+            .iter()
+            .rev()
+            .zip(new_bytes.iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        Self {
+            start: prefix,
+            old_end: old_bytes.len() - suffix,
+            new_end: new_bytes.len() - suffix,
+        }
+    }
+
+    /// Maps an offset of the analysed text to the current text.
+    pub fn map_to_new(&self, old_offset: usize) -> Option<usize> {
+        if old_offset <= self.start {
+            Some(old_offset)
+        } else if old_offset >= self.old_end {
+            Some(old_offset - self.old_end + self.new_end)
+        } else {
+            None
+        }
+    }
+
+    /// Maps an offset of the current text to the analysed text.
+    pub fn map_to_old(&self, new_offset: usize) -> Option<usize> {
+        if new_offset <= self.start {
+            Some(new_offset)
+        } else if new_offset >= self.new_end {
+            Some(new_offset - self.new_end + self.old_end)
+        } else {
+            None
+        }
+    }
+
+    /// Like [`EditMap::map_to_old`], but offsets inside the edited region map to its start.
+    pub fn map_to_old_clamped(&self, new_offset: usize) -> usize {
+        self.map_to_old(new_offset).unwrap_or(self.start)
+    }
+}
+
+impl Backend {
+    /// Goto definition against the last good analysis, returning a range in the current text.
+    pub(crate) fn last_good_definition(&self, uri: &str, position: Position) -> Option<Range> {
+        let last_good = self.last_good_map.get(uri)?;
+        let rope = self.document_map.get(uri)?;
+        let old_offset = last_good
+            .edit
+            .map_to_old(position_to_offset(position, &rope)?)?;
+        let symbol_id = symbol_at_offset(&last_good.result, old_offset)?;
+        let span = last_good.result.semantic.get_symbol_span(symbol_id);
+        let (start, end) = last_good.span_to_current(span)?;
+        Some(Range::new(
+            offset_to_position(start, &rope)?,
+            offset_to_position(end, &rope)?,
+        ))
+    }
+
+    /// Hover documentation against the last good analysis, linking to where the symbol is defined
+    /// in the current text.
+    pub(crate) fn last_good_hover(&self, uri: &Url, position: Position) -> Option<String> {
+        let key = uri.to_string();
+        let last_good = self.last_good_map.get(&key)?;
+        let rope = self.document_map.get(&key)?;
+        let old_offset = last_good
+            .edit
+            .map_to_old(position_to_offset(position, &rope)?)?;
+        let symbol_id = symbol_at_offset(&last_good.result, old_offset)?;
+        let span = last_good.result.semantic.get_symbol_span(symbol_id);
+        let defined_at = last_good
+            .edit
+            .map_to_new(span.start as usize)
+            .and_then(|start| offset_to_position(start, &rope));
+        symbol_documentation_defined_at(
+            &last_good.result,
+            &last_good.rope,
+            uri,
+            symbol_id,
+            defined_at,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, URI};
+    use l_lang::compile;
+
+    const OLD: &str =
+        "fn f() -> int {\n    return 1;\n}\n\nfn main() -> int {\n    return f();\n}\n";
+
+    fn edit_map(old: &str, new: &str) -> EditMap {
+        EditMap::between(&Rope::from_str(old), &Rope::from_str(new))
+    }
+
+    #[test]
+    fn maps_offsets_around_an_insertion() {
+        let map = edit_map("abcdef", "abXYZcdef");
+        assert_eq!(map.map_to_new(1), Some(1));
+        assert_eq!(map.map_to_new(2), Some(2));
+        assert_eq!(map.map_to_new(4), Some(7));
+        assert_eq!(map.map_to_old(7), Some(4));
+        assert_eq!(map.map_to_old(3), None);
+        assert_eq!(map.map_to_old_clamped(3), 2);
+    }
+
+    #[test]
+    fn maps_offsets_around_a_replacement() {
+        let map = edit_map("let a = 12345;", "let a = 9;");
+        assert_eq!(map.map_to_new(8), Some(8));
+        assert_eq!(map.map_to_new(10), None);
+        assert_eq!(map.map_to_new(13), Some(9));
+        assert_eq!(map.map_to_old(9), Some(13));
+    }
+
+    #[test]
+    fn keeps_repeated_text_in_the_unedited_prefix() {
+        // The common prefix and suffix may not overlap when the inserted text repeats them.
+        let map = edit_map("ab", "aab");
+        assert_eq!(map.map_to_new(0), Some(0));
+        assert_eq!(map.map_to_new(2), Some(3));
+        assert_eq!(map.map_to_old(3), Some(2));
+    }
+
+    #[test]
+    fn resolves_receiver_chains_with_the_binding_in_scope() {
+        let source = "struct Inner {\n    v: int,\n}\n\nstruct Outer {\n    inner: Inner,\n}\n\n\
+                      fn a(p: Inner) {\n    p;\n}\n\nfn b(p: Outer) {\n    p;\n}\n";
+        let last_good = LastGoodAnalysis::new(compile(source), Rope::from_str(source));
+        let semantic = &last_good.result.semantic;
+        let inner = semantic.get_symbol_at(source.find("Inner").unwrap());
+        let outer = semantic.get_symbol_at(source.find("Outer").unwrap());
+        let chain = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let in_a = source.find("p;").unwrap();
+        let in_b = source.rfind("p;").unwrap();
+        assert_eq!(last_good.struct_for_chain(in_a, &chain(&["p"])), inner);
+        assert_eq!(last_good.struct_for_chain(in_b, &chain(&["p"])), outer);
+        assert_eq!(
+            last_good.struct_for_chain(in_b, &chain(&["p", "inner"])),
+            inner
+        );
+        assert_eq!(last_good.struct_for_chain(in_a, &chain(&["p", "v"])), None);
+        assert_eq!(last_good.struct_for_chain(in_a, &chain(&["q"])), None);
+    }
+
+    #[tokio::test]
+    async fn goes_to_the_definition_in_the_current_text() {
+        let new = format!("// unfinished\nfn g( {{\n}}\n\n{OLD}");
+        let (service, _socket) = backend(&[(URI, &new)]);
+        let backend = service.inner();
+        let mut last_good = LastGoodAnalysis::new(compile(OLD), Rope::from_str(OLD));
+        last_good.update(&Rope::from_str(&new));
+        backend.last_good_map.insert(URI.to_string(), last_good);
+
+        assert_eq!(
+            backend.last_good_definition(URI, Position::new(9, 11)),
+            Some(Range::new(Position::new(4, 3), Position::new(4, 4)))
+        );
+        assert_eq!(backend.last_good_definition(URI, Position::new(1, 3)), None);
+    }
+
+    #[tokio::test]
+    async fn hover_links_to_the_definition_in_the_current_text() {
+        let new = format!("// unfinished\nfn g( {{\n}}\n\n{OLD}");
+        let (service, _socket) = backend(&[(URI, &new)]);
+        let backend = service.inner();
+        let mut last_good = LastGoodAnalysis::new(compile(OLD), Rope::from_str(OLD));
+        last_good.update(&Rope::from_str(&new));
+        backend.last_good_map.insert(URI.to_string(), last_good);
+
+        let hover = backend
+            .last_good_hover(&Url::parse(URI).unwrap(), Position::new(9, 11))
+            .unwrap();
+        assert!(hover.starts_with("```l\nfn f() -> int\n```"), "{hover}");
+        assert!(hover.contains("Defined at [main.l:5:4]"), "{hover}");
+    }
+}
@@ -0,0 +1,83 @@
+use l_lang::SymbolKind;
+use tower_lsp::lsp_types::*;
+
+use crate::syntax::IDENTIFIER_PATTERN;
+use crate::{position_to_offset, span_to_range, symbol_at_offset, Backend};
+
+impl Backend {
+    /// The declaration and every use of the function-local variable or parameter under the
+    /// cursor, so that typing over one renames them all.
+    pub(crate) fn get_linked_editing_ranges(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Option<LinkedEditingRanges> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let position = params.text_document_position_params.position;
+        let semantic_result = self.semanticast_map.get(&uri)?;
+        let rope = self.document_map.get(&uri)?;
+        let semantic = &semantic_result.semantic;
+
+        let offset = position_to_offset(position, &rope)?;
+        let symbol_id = symbol_at_offset(&semantic_result, offset)?;
+        if !matches!(
+            semantic.get_symbol_kind(symbol_id),
+            SymbolKind::Variable | SymbolKind::Parameter
+        ) {
+            return None;
+        }
+
+        let mut ranges = vec![span_to_range(semantic.get_symbol_span(symbol_id), &rope)?];
+        for ref_id in semantic.get_symbol_references(symbol_id).iter() {
+            ranges.push(span_to_range(semantic.reference_spans[*ref_id], &rope)?);
+        }
+        Some(LinkedEditingRanges {
+            ranges,
+            word_pattern: Some(IDENTIFIER_PATTERN.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, Fixture};
+
+    fn linked_ranges(marked: &str) -> Option<LinkedEditingRanges> {
+        let fixture = Fixture::new(marked);
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        service
+            .inner()
+            .get_linked_editing_ranges(LinkedEditingRangeParams {
+                text_document_position_params: fixture.position_params(),
+                work_done_progress_params: Default::default(),
+            })
+    }
+
+    #[test]
+    fn links_a_binding_with_its_uses() {
+        let ranges = linked_ranges(
+            "fn f(a: int) -> int {\n    let total = a;\n    return tot$0al + total;\n}\n",
+        )
+        .unwrap();
+        let starts = ranges
+            .ranges
+            .iter()
+            .map(|range| (range.start.line, range.start.character, range.end.character))
+            .collect::<Vec<_>>();
+        assert_eq!(starts, [(1, 8, 13), (2, 11, 16), (2, 19, 24)]);
+        assert_eq!(ranges.word_pattern.as_deref(), Some(IDENTIFIER_PATTERN));
+
+        let ranges = linked_ranges("fn f($0a: int) -> int {\n    return a;\n}\n").unwrap();
+        assert_eq!(ranges.ranges.len(), 2);
+    }
+
+    #[test]
+    fn leaves_functions_and_structs_to_rename() {
+        assert!(linked_ranges("fn $0f() {\n    f();\n}\n").is_none());
+        assert!(linked_ranges("struct $0P {\n    x: int,\n}\n").is_none());
+    }
+}
@@ -1,31 +1,67 @@
 use dashmap::DashMap;
-use l_lang::{
-    compile, find_node_at_offset, AstNode, CompileResult, Formatter, SymbolId, SymbolKind, Type,
-};
+use documentation::symbol_documentation;
+use inlay_hints::InlayHintsConfig;
+use l_lang::{compile, CompileResult, Formatter, Span, SymbolId, SymbolKind, Type};
+use last_good::LastGoodAnalysis;
 use log::debug;
 use ropey::Rope;
 use serde_json::Value;
+use std::sync::{OnceLock, RwLock};
+use syntax::{ancestors_at, SyntaxNode};
 use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::request::{GotoTypeDefinitionParams, GotoTypeDefinitionResponse};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
+use types::infer_expr_type;
+
+mod call_hierarchy;
+mod code_actions;
+mod code_lens;
+mod completion;
+mod documentation;
+mod folding;
+mod highlight;
+mod inlay_hints;
+mod interpreter;
+mod last_good;
+mod linked_editing;
+mod selection;
+mod syntax;
+mod types;
 
 #[derive(Debug)]
 struct Backend {
     client: Client,
     document_map: DashMap<String, Rope>,
     semanticast_map: DashMap<String, CompileResult>,
+    last_good_map: DashMap<String, LastGoodAnalysis>,
+    client_capabilities: OnceLock<ClientCapabilities>,
+    inlay_hints_config: RwLock<InlayHintsConfig>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let _ = self.client_capabilities.set(params.capabilities);
+        if let Some(config) = params
+            .initialization_options
+            .as_ref()
+            .and_then(InlayHintsConfig::from_settings)
+        {
+            self.set_inlay_hints_config(config);
+        }
         Ok(InitializeResult {
             server_info: None,
             offset_encoding: None,
 
             capabilities: ServerCapabilities {
                 document_formatting_provider: Some(OneOf::Left(true)),
-                inlay_hint_provider: Some(OneOf::Left(true)),
+                inlay_hint_provider: Some(OneOf::Right(InlayHintServerCapabilities::Options(
+                    InlayHintOptions {
+                        resolve_provider: Some(true),
+                        work_done_progress_options: Default::default(),
+                    },
+                ))),
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
                     TextDocumentSyncOptions {
                         open_close: Some(true),
@@ -37,14 +73,18 @@ impl LanguageServer for Backend {
                     },
                 )),
                 completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
+                    resolve_provider: Some(true),
                     trigger_characters: Some(vec![".".to_string()]),
                     work_done_progress_options: Default::default(),
                     all_commit_characters: None,
                     completion_item: None,
                 }),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec!["dummy.do_something".to_string()],
+                    commands: vec![
+                        code_lens::RUN_COMMAND.to_string(),
+                        code_lens::TEST_COMMAND.to_string(),
+                        code_actions::CHANGE_SIGNATURE_COMMAND.to_string(),
+                    ],
                     work_done_progress_options: Default::default(),
                 }),
 
@@ -87,7 +127,31 @@ impl LanguageServer for Backend {
                     ),
                 ),
                 definition_provider: Some(OneOf::Left(true)),
+                type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_INLINE,
+                            CodeActionKind::REFACTOR_REWRITE,
+                        ]),
+                        work_done_progress_options: Default::default(),
+                        resolve_provider: None,
+                    },
+                )),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(
+                    true,
+                )),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
                 rename_provider: Some(OneOf::Left(true)),
                 ..ServerCapabilities::default()
             },
@@ -135,6 +199,17 @@ impl LanguageServer for Backend {
         Ok(definition)
     }
 
+    async fn goto_type_definition(
+        &self,
+        params: GotoTypeDefinitionParams,
+    ) -> Result<Option<GotoTypeDefinitionResponse>> {
+        Ok(self.get_type_definition(params))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        Ok(self.get_hover(params))
+    }
+
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
         let uri = params.text_document_position.text_document.uri.to_string();
         let position = params.text_document_position.position;
@@ -142,6 +217,60 @@ impl LanguageServer for Backend {
         Ok(references)
     }
 
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        Ok(self.prepare_call_hierarchy_items(params))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        Ok(self.get_incoming_calls(params.item))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        Ok(self.get_outgoing_calls(params.item))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        Ok(self.get_document_highlights(params))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        Ok(self.get_code_actions(params))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        Ok(self.get_code_lenses(&params.text_document.uri))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        Ok(self.get_linked_editing_ranges(params))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        Ok(self.get_folding_ranges(params.text_document.uri.as_ref()))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        Ok(self.get_selection_ranges(params))
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
@@ -176,7 +305,11 @@ impl LanguageServer for Backend {
         &self,
         params: tower_lsp::lsp_types::InlayHintParams,
     ) -> Result<Option<Vec<InlayHint>>> {
-        Ok(self.build_inlay_hints(params.text_document.uri.as_ref()))
+        Ok(self.build_inlay_hints(params.text_document.uri.as_ref(), params.range))
+    }
+
+    async fn inlay_hint_resolve(&self, params: InlayHint) -> Result<InlayHint> {
+        Ok(self.resolve_inlay_hint(params))
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -184,6 +317,10 @@ impl LanguageServer for Backend {
         Ok(completions.map(CompletionResponse::Array))
     }
 
+    async fn completion_resolve(&self, params: CompletionItem) -> Result<CompletionItem> {
+        Ok(self.resolve_completion(params))
+    }
+
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
         let uri = params.text_document_position.text_document.uri.to_string();
         let position = params.text_document_position.position;
@@ -196,8 +333,12 @@ impl LanguageServer for Backend {
         Ok(self.format_text(params))
     }
 
-    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
         debug!("configuration changed!");
+        if let Some(config) = InlayHintsConfig::from_settings(&params.settings) {
+            self.set_inlay_hints_config(config);
+            let _ = self.client.inlay_hint_refresh().await;
+        }
     }
 
     async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
@@ -208,9 +349,37 @@ impl LanguageServer for Backend {
         debug!("watched files have changed!");
     }
 
-    async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<Value>> {
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
         debug!("command executed!");
 
+        match params.command.as_str() {
+            code_lens::RUN_COMMAND | code_lens::TEST_COMMAND => {
+                self.run_function(&params.command, params.arguments).await
+            }
+            code_actions::CHANGE_SIGNATURE_COMMAND => {
+                let failure = match self.change_signature(params.arguments) {
+                    Ok(edit) => match self.client.apply_edit(edit).await {
+                        Ok(response) if response.applied => None,
+                        Ok(response) => Some(
+                            response
+                                .failure_reason
+                                .unwrap_or_else(|| "the editor rejected the edit".to_string()),
+                        ),
+                        Err(err) => Some(err.to_string()),
+                    },
+                    Err(message) => Some(message),
+                };
+                if let Some(reason) = failure {
+                    self.client
+                        .show_message(
+                            MessageType::ERROR,
+                            format!("Cannot change signature: {reason}"),
+                        )
+                        .await
+                }
+            }
+            _ => {}
+        }
         Ok(None)
     }
 }
@@ -225,6 +394,9 @@ async fn main() {
     let (service, socket) = LspService::build(|client| Backend {
         client,
         semanticast_map: DashMap::new(),
+        last_good_map: DashMap::new(),
+        client_capabilities: OnceLock::new(),
+        inlay_hints_config: RwLock::new(InlayHintsConfig::default()),
         document_map: DashMap::new(),
     })
     .finish();
@@ -233,6 +405,30 @@ async fn main() {
 }
 
 impl Backend {
+    fn inlay_hints_config(&self) -> InlayHintsConfig {
+        self.inlay_hints_config
+            .read()
+            .map(|config| *config)
+            .unwrap_or_default()
+    }
+
+    fn set_inlay_hints_config(&self, config: InlayHintsConfig) {
+        if let Ok(mut current) = self.inlay_hints_config.write() {
+            *current = config;
+        }
+    }
+
+    /// Whether the client lists `command` under `experimental.commands`, which is how the VS Code
+    /// extension announces the client-side commands it provides.
+    fn supports_client_command(&self, command: &str) -> bool {
+        self.client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.experimental.as_ref())
+            .and_then(|experimental| experimental.get("commands"))
+            .and_then(Value::as_array)
+            .is_some_and(|commands| commands.iter().any(|name| name.as_str() == Some(command)))
+    }
+
     fn format_text(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
         let uri = params.text_document.uri.to_string();
         let rope = self.document_map.get(&uri)?;
@@ -251,74 +447,124 @@ impl Backend {
         }])
     }
 
-    fn build_inlay_hints(&self, uri: &str) -> Option<Vec<InlayHint>> {
+    /// The inlay hints positioned inside `range`.
+    fn build_inlay_hints(&self, uri: &str, range: Range) -> Option<Vec<InlayHint>> {
         let semantic_result = self.semanticast_map.get(uri)?;
         let rope = self.document_map.get(uri)?;
-        let bindings = &semantic_result.semantic.bindings;
-        let hints = bindings
-            .iter_enumerated()
-            .filter_map(|(symbol_id, type_info)| {
-                if semantic_result.semantic.get_symbol_kind(symbol_id)
-                    != l_lang::SymbolKind::Variable
-                {
-                    return None;
-                }
-                let span = semantic_result.semantic.get_symbol_span(symbol_id);
-                let end = offset_to_position(span.end as usize, &rope)?;
-                let inly_hint_parts = match type_info.ty {
-                    Type::Struct(id) => {
-                        let mut parts = vec![];
-                        parts.push(InlayHintLabelPart {
-                            value: ": ".to_string(),
-                            ..Default::default()
-                        });
-                        let span = semantic_result.semantic.get_symbol_span(id);
-                        let start = offset_to_position(span.start as usize, &rope)?;
-                        let end = offset_to_position(span.end as usize, &rope)?;
-                        let location = Location::new(
-                            Url::parse(uri)
-                                .unwrap_or_else(|_| Url::from_directory_path(uri).unwrap()),
-                            Range::new(start, end),
-                        );
-                        parts.push(InlayHintLabelPart {
-                            value: type_info.ty.format_literal_type(&semantic_result.semantic),
-                            location: Some(location),
-                            ..Default::default()
-                        });
-                        InlayHintLabel::LabelParts(parts)
+        let config = self.inlay_hints_config();
+        let to_offset = |position: Position| {
+            let line = (position.line as usize).min(rope.len_lines());
+            let char = rope.line_to_char(line) + position.character as usize;
+            rope.char_to_byte(char.min(rope.len_chars())) as u32
+        };
+        let visible = Span {
+            start: to_offset(range.start),
+            end: to_offset(range.end),
+        };
+        let mut hints = vec![];
+        if config.variable_types {
+            syntax::walk_overlapping(
+                SyntaxNode::File(semantic_result.program.file()),
+                visible,
+                &mut |node| {
+                    let SyntaxNode::Stmt(l_lang::Stmt::Let(let_stmt)) = node else {
+                        return;
+                    };
+                    let Some(name) = let_stmt.name.as_ref() else {
+                        return;
+                    };
+                    if !inlay_hints::is_visible(visible, name.span.end) {
+                        return;
                     }
-                    _ => InlayHintLabel::String(format!(
-                        ": {}",
-                        type_info.ty.format_literal_type(&semantic_result.semantic)
-                    )),
-                };
-                Some(InlayHint {
-                    position: Position::new(end.line, end.character),
-                    label: inly_hint_parts,
-                    kind: Some(InlayHintKind::TYPE),
-                    text_edits: None,
-                    tooltip: None,
-                    padding_left: Some(true),
-                    padding_right: Some(false),
-                    data: None,
-                })
-            })
-            .collect::<Vec<_>>();
+                    let Some(symbol_id) = semantic_result
+                        .semantic
+                        .get_symbol_at(name.span.start as usize)
+                    else {
+                        return;
+                    };
+                    let Some(type_info) = semantic_result.semantic.get_symbol_type(symbol_id)
+                    else {
+                        return;
+                    };
+                    let Some(end) = offset_to_position(name.span.end as usize, &rope) else {
+                        return;
+                    };
+                    let inly_hint_parts =
+                        match type_definition_location(&semantic_result, &rope, uri, &type_info.ty)
+                        {
+                            Some(location) => {
+                                let parts = vec![
+                                    InlayHintLabelPart {
+                                        value: ": ".to_string(),
+                                        ..Default::default()
+                                    },
+                                    InlayHintLabelPart {
+                                        value: type_info
+                                            .ty
+                                            .format_literal_type(&semantic_result.semantic),
+                                        location: Some(location),
+                                        ..Default::default()
+                                    },
+                                ];
+                                InlayHintLabel::LabelParts(parts)
+                            }
+                            None => InlayHintLabel::String(format!(
+                                ": {}",
+                                type_info.ty.format_literal_type(&semantic_result.semantic)
+                            )),
+                        };
+                    hints.push(InlayHint {
+                        position: Position::new(end.line, end.character),
+                        label: inly_hint_parts,
+                        kind: Some(InlayHintKind::TYPE),
+                        text_edits: None,
+                        tooltip: None,
+                        padding_left: Some(true),
+                        padding_right: Some(false),
+                        data: inlay_hints::hint_data(uri, name.span.start),
+                    });
+                },
+            );
+        }
+        if config.parameter_names {
+            hints.extend(inlay_hints::parameter_hints(
+                &semantic_result,
+                &rope,
+                visible,
+            ));
+        }
+        if config.return_types {
+            hints.extend(inlay_hints::return_type_hints(
+                uri,
+                &semantic_result,
+                &rope,
+                visible,
+            ));
+        }
+        hints.extend(inlay_hints::closing_brace_hints(
+            &semantic_result,
+            &rope,
+            &config,
+            visible,
+        ));
 
         Some(hints)
     }
 
     fn get_definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
-        let uri = params
-            .text_document_position_params
-            .text_document
-            .uri
-            .to_string();
+        let uri = params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
+        let key = uri.to_string();
+        let range = self
+            .definition_range(&key, position)
+            .or_else(|| self.last_good_definition(&key, position))?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+    }
 
-        let rope = self.document_map.get(&uri)?;
+    fn definition_range(&self, uri: &str, position: Position) -> Option<Range> {
+        let rope = self.document_map.get(uri)?;
 
-        let compilation_result = self.semanticast_map.get(&uri)?;
+        let compilation_result = self.semanticast_map.get(uri)?;
         let offset = position_to_offset(position, &rope)?;
         if let Some(interval) = compilation_result
             .semantic
@@ -328,11 +574,7 @@ impl Backend {
         {
             let start = offset_to_position(interval.start, &rope)?;
             let end = offset_to_position(interval.stop, &rope)?;
-            let location = Location::new(
-                params.text_document_position_params.text_document.uri,
-                Range::new(start, end),
-            );
-            return Some(GotoDefinitionResponse::Scalar(location));
+            return Some(Range::new(start, end));
         };
         let ref_id = compilation_result
             .semantic
@@ -344,12 +586,75 @@ impl Backend {
         let symbol_span = compilation_result.semantic.get_symbol_span(symbol_id);
         let start = offset_to_position(symbol_span.start as usize, &rope)?;
         let end = offset_to_position(symbol_span.end as usize, &rope)?;
-        let location = Location::new(
-            params.text_document_position_params.text_document.uri,
-            Range::new(start, end),
-        );
+        Some(Range::new(start, end))
+    }
 
-        Some(GotoDefinitionResponse::Scalar(location))
+    fn get_type_definition(
+        &self,
+        params: GotoTypeDefinitionParams,
+    ) -> Option<GotoTypeDefinitionResponse> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let position = params.text_document_position_params.position;
+        let rope = self.document_map.get(&uri)?;
+        let semantic_result = self.semanticast_map.get(&uri)?;
+        let offset = position_to_offset(position, &rope)?;
+
+        // A declared variable, parameter or field, otherwise the innermost expression
+        let ty = match semantic_result
+            .semantic
+            .span_to_symbol
+            .find(offset, offset + 1)
+            .next()
+        {
+            Some(interval) => match semantic_result.semantic.get_symbol_kind(interval.val) {
+                SymbolKind::Struct => Type::Struct(interval.val),
+                _ => semantic_result
+                    .semantic
+                    .get_symbol_type(interval.val)?
+                    .ty
+                    .clone(),
+            },
+            None => {
+                let expr = ancestors_at(semantic_result.program.file(), offset as u32)
+                    .into_iter()
+                    .rev()
+                    .find_map(|node| match node {
+                        SyntaxNode::Expr(expr) => Some(expr),
+                        _ => None,
+                    })?;
+                infer_expr_type(expr, &semantic_result, &rope)?
+            }
+        };
+        let location = type_definition_location(&semantic_result, &rope, &uri, &ty)?;
+        Some(GotoTypeDefinitionResponse::Scalar(location))
+    }
+
+    fn get_hover(&self, params: HoverParams) -> Option<Hover> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let value = self
+            .hover_documentation(&uri, position)
+            .or_else(|| self.last_good_hover(&uri, position))?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    fn hover_documentation(&self, uri: &Url, position: Position) -> Option<String> {
+        let key = uri.to_string();
+        let rope = self.document_map.get(&key)?;
+        let semantic_result = self.semanticast_map.get(&key)?;
+        let offset = position_to_offset(position, &rope)?;
+        let symbol_id = symbol_at_offset(&semantic_result, offset)?;
+        symbol_documentation(&semantic_result, &rope, uri, symbol_id)
     }
 
     fn get_references(
@@ -413,164 +718,19 @@ impl Backend {
         &self,
         field_expr: &l_lang::ExprField,
         semantic_result: &CompileResult,
+        rope: &Rope,
     ) -> Option<SymbolId> {
-        let mut access_arr = vec![];
-        let mut cur = field_expr.object.as_ref()?;
-        loop {
-            match cur.as_ref() {
-                l_lang::Expr::Field(field_expr) => {
-                    access_arr.push(field_expr.field.as_ref()?.name.clone());
-                    cur = field_expr.object.as_ref()?;
-                }
-                l_lang::Expr::Name(_name_expr) => {
-                    break;
-                }
-                _ => {
-                    return None;
-                }
-            }
-        }
-        access_arr.reverse();
-
-        let reference_id = semantic_result
-            .semantic
-            .get_reference_at(field_expr.object.as_ref()?.span().start as usize)?;
-        let symbol_id = semantic_result.semantic.references[reference_id]?;
-        let ty_info = semantic_result.semantic.get_symbol_type(symbol_id)?;
-        let Type::Struct(mut struct_id) = ty_info.ty else {
+        let receiver = field_expr.object.as_deref()?;
+        let Type::Struct(struct_id) = infer_expr_type(receiver, semantic_result, rope)? else {
             return None;
         };
-
-        for field_name in access_arr {
-            let struct_def = semantic_result.semantic.structs.get(&struct_id)?;
-            let field = struct_def.fields.iter().find(|f| f.name == field_name)?;
-            let Type::Struct(next_struct_id) = field.ty else {
-                return None;
-            };
-            struct_id = next_struct_id;
-        }
         Some(struct_id)
     }
 
-    fn get_completion(&self, params: CompletionParams) -> Option<Vec<CompletionItem>> {
-        let text_doc_position = params.text_document_position;
-        let uri = text_doc_position.text_document.uri.to_string();
-        let semantic_result = self.semanticast_map.get(&uri)?;
-        let rope = self.document_map.get(&uri)?;
-        let offset = position_to_offset(text_doc_position.position, &rope)?;
-
-        let mut items = Vec::new();
-
-        // Try to find the AST node at the current position
-        if let Some(nearest_node) =
-            find_node_at_offset(semantic_result.program.file(), offset as u32)
-        {
-            match nearest_node {
-                // Field access completion: suggest available fields/members
-                AstNode::ExprField(field_expr) => {
-                    let struct_id = self.get_struct_id_from_field(field_expr, &semantic_result)?;
-                    let struct_def = semantic_result.semantic.structs.get(&struct_id)?;
-                    struct_def.fields.iter().for_each(|field| {
-                        items.push(CompletionItem {
-                            label: field.name.clone(),
-                            kind: Some(CompletionItemKind::FIELD),
-                            detail: Some(format!(
-                                ": {}",
-                                field.ty.format_literal_type(&semantic_result.semantic)
-                            )),
-                            insert_text: Some(field.name.clone()),
-                            ..Default::default()
-                        });
-                    });
-                }
-                _ => {
-                    // Default: suggest all available symbols
-                    let bindings = &semantic_result.semantic.bindings;
-                    bindings
-                        .iter_enumerated()
-                        .for_each(|(symbol_id, type_info)| {
-                            let symbol_kind = semantic_result.semantic.get_symbol_kind(symbol_id);
-                            let span = semantic_result.semantic.get_symbol_span(symbol_id);
-
-                            let name_slice =
-                                rope.byte_slice(span.start as usize..span.end as usize);
-                            if let Ok(name) = std::str::from_utf8(
-                                name_slice.bytes().collect::<Vec<_>>().as_slice(),
-                            ) {
-                                let (kind, detail) = match symbol_kind {
-                                    l_lang::SymbolKind::Variable => (
-                                        Some(CompletionItemKind::VARIABLE),
-                                        Some(format!(
-                                            ": {}",
-                                            type_info
-                                                .ty
-                                                .format_literal_type(&semantic_result.semantic)
-                                        )),
-                                    ),
-                                    l_lang::SymbolKind::Function => {
-                                        (Some(CompletionItemKind::FUNCTION), None)
-                                    }
-                                    l_lang::SymbolKind::Struct => {
-                                        (Some(CompletionItemKind::STRUCT), None)
-                                    }
-                                    _ => (None, None),
-                                };
-
-                                items.push(CompletionItem {
-                                    label: name.to_string(),
-                                    kind,
-                                    detail,
-                                    insert_text: Some(name.to_string()),
-                                    ..Default::default()
-                                });
-                            }
-                        });
-                }
-            }
-        } else {
-            // No node found, suggest all available symbols
-            let bindings = &semantic_result.semantic.bindings;
-            bindings
-                .iter_enumerated()
-                .for_each(|(symbol_id, type_info)| {
-                    let symbol_kind = semantic_result.semantic.get_symbol_kind(symbol_id);
-                    let span = semantic_result.semantic.get_symbol_span(symbol_id);
-
-                    let name_slice = rope.byte_slice(span.start as usize..span.end as usize);
-                    if let Ok(name) =
-                        std::str::from_utf8(name_slice.bytes().collect::<Vec<_>>().as_slice())
-                    {
-                        let (kind, detail) = match symbol_kind {
-                            l_lang::SymbolKind::Variable => (
-                                Some(CompletionItemKind::VARIABLE),
-                                Some(format!(
-                                    ": {}",
-                                    type_info.ty.format_literal_type(&semantic_result.semantic)
-                                )),
-                            ),
-                            l_lang::SymbolKind::Function => {
-                                (Some(CompletionItemKind::FUNCTION), None)
-                            }
-                            l_lang::SymbolKind::Struct => (Some(CompletionItemKind::STRUCT), None),
-                            _ => (None, None),
-                        };
-
-                        items.push(CompletionItem {
-                            label: name.to_string(),
-                            kind,
-                            detail,
-                            insert_text: Some(name.to_string()),
-                            ..Default::default()
-                        });
-                    }
-                });
-        }
-        Some(items)
-    }
-
     async fn on_change(&self, item: TextDocumentChange<'_>) {
         let rope = Rope::from_str(item.text);
         let compile_result = compile(item.text);
+        let parsed_cleanly = compile_result.diagnostics.is_empty();
         let mut diagnostics = compile_result
             .diagnostics
             .iter()
@@ -618,6 +778,25 @@ impl Backend {
         self.client
             .publish_diagnostics(uri, diagnostics, None)
             .await;
+        if parsed_cleanly {
+            self.last_good_map.remove(&item.uri);
+        } else {
+            // Keep the previous analysis around if it came from a clean parse, so that
+            // completion and navigation keep working while the text is unparseable.
+            let previous = self.semanticast_map.remove(&item.uri);
+            let previous_rope = self.document_map.remove(&item.uri);
+            if let (Some((_, previous)), Some((_, previous_rope))) = (previous, previous_rope) {
+                if previous.diagnostics.is_empty() {
+                    self.last_good_map.insert(
+                        item.uri.clone(),
+                        LastGoodAnalysis::new(previous, previous_rope),
+                    );
+                }
+            }
+            if let Some(mut last_good) = self.last_good_map.get_mut(&item.uri) {
+                last_good.update(&rope);
+            }
+        }
         self.semanticast_map
             .insert(item.uri.clone(), compile_result);
         self.document_map.insert(item.uri.clone(), rope);
@@ -814,3 +993,151 @@ fn position_to_offset(position: Position, rope: &Rope) -> Option<usize> {
     let slice = rope.slice(0..line_char_offset + position.character as usize);
     Some(slice.len_bytes())
 }
+
+/// The location of the struct declaration a type refers to, if it is a struct type.
+fn type_definition_location(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    uri: &str,
+    ty: &Type,
+) -> Option<Location> {
+    let Type::Struct(id) = ty else {
+        return None;
+    };
+    let span = semantic_result.semantic.get_symbol_span(*id);
+    Some(Location::new(
+        Url::parse(uri).unwrap_or_else(|_| Url::from_directory_path(uri).unwrap()),
+        span_to_range(span, rope)?,
+    ))
+}
+
+/// The symbol declared at `offset`, or the one referenced there.
+fn symbol_at_offset(semantic_result: &CompileResult, offset: usize) -> Option<SymbolId> {
+    let semantic = &semantic_result.semantic;
+    if let Some(interval) = semantic.span_to_symbol.find(offset, offset + 1).next() {
+        return Some(interval.val);
+    }
+    let reference_id = semantic.get_reference_at(offset)?;
+    semantic.references[reference_id]
+}
+
+fn span_to_range(span: Span, rope: &Rope) -> Option<Range> {
+    let start = offset_to_position(span.start as usize, rope)?;
+    let end = offset_to_position(span.end as usize, rope)?;
+    Some(Range::new(start, end))
+}
+
+fn span_text(span: Span, rope: &Rope) -> Option<String> {
+    let slice = rope.get_byte_slice(span.start as usize..span.end as usize)?;
+    Some(slice.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, Fixture, URI};
+
+    /// Where the name of the type declaration that goto type definition finds at the cursor starts.
+    fn type_definition(marked: &str) -> Option<Position> {
+        let fixture = Fixture::new(marked);
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        let response = service
+            .inner()
+            .get_type_definition(GotoTypeDefinitionParams {
+                text_document_position_params: fixture.position_params(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })?;
+        let GotoTypeDefinitionResponse::Scalar(location) = response else {
+            return None;
+        };
+        Some(location.range.start)
+    }
+
+    const POINT: &str = "struct Point {\n    x: int,\n    origin: Origin,\n}\n\n\
+                         struct Origin {\n    x: int,\n}\n\n";
+    const POINT_NAME: Position = Position {
+        line: 0,
+        character: 7,
+    };
+    const ORIGIN_NAME: Position = Position {
+        line: 5,
+        character: 7,
+    };
+
+    #[test]
+    fn goes_to_the_type_of_bindings_and_fields() {
+        let source = |marked| format!("{POINT}fn f(p: Point) -> int {{\n    {marked}\n}}\n");
+        assert_eq!(type_definition(&source("return $0p.x;")), Some(POINT_NAME));
+        assert_eq!(
+            type_definition(&source("let o = p.origin;\n    return $0o.x;")),
+            Some(ORIGIN_NAME)
+        );
+        assert_eq!(
+            type_definition(&format!("{POINT}fn f($0p: Point) {{\n}}\n")),
+            Some(POINT_NAME)
+        );
+        assert_eq!(
+            type_definition(&POINT.replacen("origin:", "$0origin:", 1)),
+            Some(ORIGIN_NAME)
+        );
+    }
+
+    #[test]
+    fn goes_to_the_type_of_expressions() {
+        let source = format!("{POINT}fn f(p: Point) -> Origin {{\n    return (p.or$0igin);\n}}\n");
+        assert_eq!(type_definition(&source), Some(ORIGIN_NAME));
+    }
+
+    #[test]
+    fn finds_nothing_for_primitive_types() {
+        assert_eq!(
+            type_definition(&format!(
+                "{POINT}fn f(p: Point) -> int {{\n    return p.$0x;\n}}\n"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_inlay_hints_for_the_requested_range_only() {
+        let source = "struct P {\n    x: int,\n}\n\nfn f(x: int) {\n    let a = P { x: 1 };\n    \
+                      let b = 2;\n    f(b);\n    return a;\n}\n";
+        let (service, _socket) = backend(&[(URI, source)]);
+        let hints = |start: u32, end: u32| {
+            let mut hints = service
+                .inner()
+                .build_inlay_hints(
+                    URI,
+                    Range::new(Position::new(start, 0), Position::new(end, 0)),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|hint| {
+                    let label = match hint.label {
+                        InlayHintLabel::String(label) => label,
+                        InlayHintLabel::LabelParts(parts) => {
+                            parts.into_iter().map(|part| part.value).collect()
+                        }
+                    };
+                    (hint.position.line, label)
+                })
+                .collect::<Vec<_>>();
+            hints.sort();
+            hints
+        };
+        assert_eq!(
+            hints(0, 100),
+            [
+                (4, "-> P".to_string()),
+                (5, ": P".to_string()),
+                (6, ": int".to_string()),
+                (7, "x:".to_string()),
+            ]
+        );
+        assert_eq!(
+            hints(6, 8),
+            [(6, ": int".to_string()), (7, "x:".to_string())]
+        );
+    }
+}
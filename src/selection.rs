@@ -0,0 +1,152 @@
+use l_lang::{Expr, Span};
+use tower_lsp::lsp_types::*;
+
+use crate::syntax::{ancestors_at, SyntaxNode};
+use crate::{position_to_offset, span_to_range, Backend};
+
+impl Backend {
+    /// For each position, the chain of enclosing AST nodes from the identifier under the cursor
+    /// out to the whole file. Positions that can't be resolved get an empty range so the
+    /// response still has one entry per position.
+    pub(crate) fn get_selection_ranges(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Option<Vec<SelectionRange>> {
+        let uri = params.text_document.uri.to_string();
+        let semantic_result = self.semanticast_map.get(&uri)?;
+        let rope = self.document_map.get(&uri)?;
+
+        let resolve = |position: Position| {
+            let offset = position_to_offset(position, &rope)?;
+            let ancestors = ancestors_at(semantic_result.program.file(), offset as u32);
+
+            let mut spans = ancestors.iter().map(SyntaxNode::span).collect::<Vec<_>>();
+            let identifier = semantic_result
+                .semantic
+                .span_to_symbol
+                .find(offset, offset + 1)
+                .next()
+                .map(|interval| (interval.start, interval.stop))
+                .or_else(|| {
+                    semantic_result
+                        .semantic
+                        .span_to_reference
+                        .find(offset, offset + 1)
+                        .next()
+                        .map(|interval| (interval.start, interval.stop))
+                })
+                .map(|(start, stop)| Span {
+                    start: start as u32,
+                    end: stop as u32,
+                })
+                .or_else(|| match ancestors.last() {
+                    Some(SyntaxNode::Expr(Expr::Field(field_expr))) => {
+                        field_expr.field.as_ref().map(|field| field.span)
+                    }
+                    _ => None,
+                });
+            spans.extend(identifier);
+            spans.dedup();
+
+            let mut selection_range: Option<SelectionRange> = None;
+            for span in spans {
+                selection_range = Some(SelectionRange {
+                    range: span_to_range(span, &rope)?,
+                    parent: selection_range.map(Box::new),
+                });
+            }
+            selection_range
+        };
+
+        Some(
+            params
+                .positions
+                .into_iter()
+                .map(|position| {
+                    resolve(position).unwrap_or(SelectionRange {
+                        range: Range::new(position, position),
+                        parent: None,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_actions::test_support::{backend, Fixture};
+    use crate::span_text;
+
+    /// The selection ranges for each of `positions` in the document of `fixture`.
+    fn selection_ranges(fixture: &Fixture, positions: Vec<Position>) -> Vec<SelectionRange> {
+        let (service, _socket) = backend(&fixture.with_others(&[]));
+        service
+            .inner()
+            .get_selection_ranges(SelectionRangeParams {
+                text_document: TextDocumentIdentifier::new(fixture.uri.clone()),
+                positions,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap()
+    }
+
+    /// The text of each range the selection expands through from the cursor, innermost first.
+    fn expansions(marked: &str) -> Vec<String> {
+        let fixture = Fixture::new(marked);
+        let position = fixture.position_params().position;
+        let mut selection_range = selection_ranges(&fixture, vec![position]).pop();
+        let mut texts = vec![];
+        while let Some(current) = selection_range {
+            let start = position_to_offset(current.range.start, &fixture.rope).unwrap();
+            let end = position_to_offset(current.range.end, &fixture.rope).unwrap();
+            let span = Span {
+                start: start as u32,
+                end: end as u32,
+            };
+            texts.push(span_text(span, &fixture.rope).unwrap());
+            selection_range = current.parent.map(|parent| *parent);
+        }
+        texts
+    }
+
+    #[test]
+    fn expands_from_the_identifier_to_the_file() {
+        let source = "fn f(a: int) -> int {\n    return (a + 1$0) * 2;\n}\n";
+        assert_eq!(
+            expansions(source),
+            [
+                "1",
+                "a + 1",
+                "(a + 1)",
+                "(a + 1) * 2",
+                "return (a + 1) * 2;",
+                "{\n    return (a + 1) * 2;\n}",
+                "fn f(a: int) -> int {\n    return (a + 1) * 2;\n}",
+                "fn f(a: int) -> int {\n    return (a + 1) * 2;\n}\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn starts_at_the_field_name_of_an_access() {
+        let source = "struct P {\n    x: int,\n}\n\nfn f(p: P) -> int {\n    return p.$0x;\n}\n";
+        assert_eq!(expansions(source)[..3], ["x", "p.x", "return p.x;"]);
+    }
+
+    #[test]
+    fn answers_every_position() {
+        let fixture = Fixture::new("fn $0f() {\n}\n");
+        let ranges = selection_ranges(&fixture, vec![Position::new(0, 3), Position::new(9, 0)]);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(
+            ranges[1],
+            SelectionRange {
+                range: Range::new(Position::new(9, 0), Position::new(9, 0)),
+                parent: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use l_lang::{Block, Expr, File, Function, Item, Span, Stmt, Struct};
+
+/// Identifiers as the l-lang lexer reads them: an ASCII letter or `_`, then ASCII letters, digits
+/// and `_`. Editors that match identifiers themselves get this pattern; [`is_ident_char`] is the
+/// same definition for scanning text on the server.
+pub const IDENTIFIER_PATTERN: &str = "[A-Za-z_][A-Za-z0-9_]*";
+
+/// Whether `c` may appear in an identifier, see [`IDENTIFIER_PATTERN`].
+pub fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// A borrowed view over the AST nodes the server walks through.
+#[derive(Debug, Clone, Copy)]
+pub enum SyntaxNode<'a> {
+    File(&'a File),
+    Function(&'a Function),
+    Struct(&'a Struct),
+    Block(&'a Block),
+    Stmt(&'a Stmt),
+    Expr(&'a Expr),
+}
+
+impl<'a> SyntaxNode<'a> {
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxNode::File(file) => file.span,
+            SyntaxNode::Function(function) => function.span,
+            SyntaxNode::Struct(struct_decl) => struct_decl.span,
+            SyntaxNode::Block(block) => block.span,
+            SyntaxNode::Stmt(stmt) => stmt.span(),
+            SyntaxNode::Expr(expr) => expr.span(),
+        }
+    }
+
+    pub fn children(&self) -> Vec<SyntaxNode<'a>> {
+        let mut children = vec![];
+        match *self {
+            SyntaxNode::File(file) => {
+                for item in file.items.iter() {
+                    children.push(match item {
+                        Item::Function(function) => SyntaxNode::Function(function),
+                        Item::Struct(struct_decl) => SyntaxNode::Struct(struct_decl),
+                    });
+                }
+            }
+            SyntaxNode::Function(function) => {
+                if let Some(body) = function.body.as_ref() {
+                    children.push(SyntaxNode::Block(body));
+                }
+            }
+            SyntaxNode::Struct(_) => {}
+            SyntaxNode::Block(block) => {
+                children.extend(block.stmts.iter().map(SyntaxNode::Stmt));
+            }
+            SyntaxNode::Stmt(stmt) => match stmt {
+                Stmt::Let(let_stmt) => {
+                    children.extend(let_stmt.init.as_deref().map(SyntaxNode::Expr))
+                }
+                Stmt::Return(return_stmt) => {
+                    children.extend(return_stmt.value.as_deref().map(SyntaxNode::Expr))
+                }
+                Stmt::Expr(expr_stmt) => {
+                    children.extend(expr_stmt.expr.as_deref().map(SyntaxNode::Expr))
+                }
+                Stmt::Block(block) => children.push(SyntaxNode::Block(block)),
+            },
+            SyntaxNode::Expr(expr) => match expr {
+                Expr::Field(field_expr) => {
+                    children.extend(field_expr.object.as_deref().map(SyntaxNode::Expr));
+                }
+                Expr::Call(call_expr) => {
+                    children.extend(call_expr.callee.as_deref().map(SyntaxNode::Expr));
+                    children.extend(call_expr.args.iter().map(SyntaxNode::Expr));
+                }
+                Expr::Struct(struct_expr) => {
+                    children.extend(
+                        struct_expr
+                            .fields
+                            .iter()
+                            .filter_map(|field| field.value.as_deref())
+                            .map(SyntaxNode::Expr),
+                    );
+                }
+                Expr::Binary(binary_expr) => {
+                    children.extend(binary_expr.lhs.as_deref().map(SyntaxNode::Expr));
+                    children.extend(binary_expr.rhs.as_deref().map(SyntaxNode::Expr));
+                }
+                Expr::Paren(paren_expr) => {
+                    children.extend(paren_expr.expr.as_deref().map(SyntaxNode::Expr));
+                }
+                Expr::Name(_) | Expr::Literal(_) => {}
+            },
+        }
+        children
+    }
+}
+
+/// Returns every node whose span contains `offset`, from the file down to the innermost node.
+pub fn ancestors_at(file: &File, offset: u32) -> Vec<SyntaxNode<'_>> {
+    let mut ancestors = vec![SyntaxNode::File(file)];
+    let mut cur = SyntaxNode::File(file);
+    while let Some(child) = cur.children().into_iter().find(|child| {
+        let span = child.span();
+        span.start <= offset && offset <= span.end
+    }) {
+        ancestors.push(child);
+        cur = child;
+    }
+    ancestors
+}
+
+/// Returns every node whose span contains all of `span`, from the file down to the innermost node.
+pub fn ancestors_of_span(file: &File, span: Span) -> Vec<SyntaxNode<'_>> {
+    let mut ancestors = vec![SyntaxNode::File(file)];
+    let mut cur = SyntaxNode::File(file);
+    while let Some(child) = cur.children().into_iter().find(|child| {
+        let child_span = child.span();
+        child_span.start <= span.start && span.end <= child_span.end
+    }) {
+        ancestors.push(child);
+        cur = child;
+    }
+    ancestors
+}
+
+/// Finds the function declaration whose name is the given symbol span.
+pub fn find_function_by_name_span(file: &File, name_span: Span) -> Option<&Function> {
+    file.items.iter().find_map(|item| match item {
+        Item::Function(function)
+            if function.name.as_ref().map(|name| name.span) == Some(name_span) =>
+        {
+            Some(function)
+        }
+        _ => None,
+    })
+}
+
+/// Finds the struct declaration whose name is the given symbol span.
+pub fn find_struct_by_name_span(file: &File, name_span: Span) -> Option<&Struct> {
+    file.items.iter().find_map(|item| match item {
+        Item::Struct(struct_decl)
+            if struct_decl.name.as_ref().map(|name| name.span) == Some(name_span) =>
+        {
+            Some(struct_decl)
+        }
+        _ => None,
+    })
+}
+
+/// Visits `node` and all of its descendants in source order.
+pub fn walk<'a>(node: SyntaxNode<'a>, visit: &mut impl FnMut(SyntaxNode<'a>)) {
+    visit(node);
+    for child in node.children() {
+        walk(child, visit);
+    }
+}
+
+/// Like [`walk`], but skips the nodes that don't overlap `span`, along with their descendants.
+pub fn walk_overlapping<'a>(
+    node: SyntaxNode<'a>,
+    span: Span,
+    visit: &mut impl FnMut(SyntaxNode<'a>),
+) {
+    let node_span = node.span();
+    if node_span.end < span.start || span.end < node_span.start {
+        return;
+    }
+    visit(node);
+    for child in node.children() {
+        walk_overlapping(child, span, visit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_are_ascii_like_in_the_lexer() {
+        assert!("a_Z09".chars().all(is_ident_char));
+        assert!(!['é', 'ß', '٣', '-', '.', ' ']
+            .into_iter()
+            .any(is_ident_char));
+    }
+}
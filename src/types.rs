@@ -0,0 +1,274 @@
+use l_lang::{CompileResult, Expr, File, Function, Literal, Stmt, SymbolKind, Type};
+use ropey::Rope;
+
+use crate::span_text;
+use crate::syntax::{ancestors_at, find_function_by_name_span, walk, SyntaxNode};
+
+/// Resolves a written type annotation such as `int` or `Point` to a [`Type`].
+pub fn resolve_type_name(name: &str, semantic_result: &CompileResult, rope: &Rope) -> Option<Type> {
+    match name {
+        "int" => Some(Type::Int),
+        "bool" => Some(Type::Bool),
+        "string" => Some(Type::String),
+        _ => semantic_result
+            .semantic
+            .structs
+            .keys()
+            .find(|struct_id| {
+                let span = semantic_result.semantic.get_symbol_span(**struct_id);
+                span_text(span, rope).as_deref() == Some(name)
+            })
+            .map(|struct_id| Type::Struct(*struct_id)),
+    }
+}
+
+/// How deep inference follows calls into functions without a `->` annotation.
+const MAX_INFERENCE_DEPTH: u8 = 8;
+
+/// The return type of a function: its `->` annotation, or else the type every `return` in its
+/// body agrees on, including those in nested blocks. A `return` without a value, or one whose type
+/// is unknown or differs from the others, leaves it unknown.
+pub fn function_return_type(
+    function: &Function,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+) -> Option<Type> {
+    function_return_type_with_depth(function, semantic_result, rope, MAX_INFERENCE_DEPTH)
+}
+
+fn function_return_type_with_depth(
+    function: &Function,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    depth: u8,
+) -> Option<Type> {
+    if let Some(ret_ty) = function.ret_ty.as_ref() {
+        return resolve_type_name(&ret_ty.name, semantic_result, rope);
+    }
+    let body = function.body.as_ref()?;
+    let mut values = vec![];
+    walk(SyntaxNode::Block(body), &mut |node| {
+        if let SyntaxNode::Stmt(Stmt::Return(return_stmt)) = node {
+            values.push(return_stmt.value.as_deref());
+        }
+    });
+    let mut types = values.into_iter().map(|value| {
+        infer_expr_type_with_depth(value?, semantic_result, rope, depth)
+            .filter(|ty| *ty != Type::Unknown)
+    });
+    let ty = types.next()??;
+    types.all(|other| other.as_ref() == Some(&ty)).then_some(ty)
+}
+
+/// Computes the type of an arbitrary expression: names, field accesses, calls, struct literals,
+/// parenthesised expressions, literals and arithmetic.
+pub fn infer_expr_type(expr: &Expr, semantic_result: &CompileResult, rope: &Rope) -> Option<Type> {
+    infer_expr_type_with_depth(expr, semantic_result, rope, MAX_INFERENCE_DEPTH)
+}
+
+fn infer_expr_type_with_depth(
+    expr: &Expr,
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    depth: u8,
+) -> Option<Type> {
+    let semantic = &semantic_result.semantic;
+    match expr {
+        Expr::Name(name_expr) => {
+            let reference_id = semantic.get_reference_at(name_expr.span.start as usize)?;
+            let symbol_id = semantic.references[reference_id]?;
+            Some(semantic.get_symbol_type(symbol_id)?.ty.clone())
+        }
+        Expr::Field(field_expr) => {
+            let object = field_expr.object.as_deref()?;
+            let Type::Struct(struct_id) =
+                infer_expr_type_with_depth(object, semantic_result, rope, depth)?
+            else {
+                return None;
+            };
+            let field_name = field_expr.field.as_ref()?;
+            let field = semantic
+                .structs
+                .get(&struct_id)?
+                .fields
+                .iter()
+                .find(|field| field.name == field_name.name)?;
+            Some(field.ty.clone())
+        }
+        Expr::Call(call_expr) => {
+            if depth == 0 {
+                return None;
+            }
+            let function = resolve_callee(call_expr.callee.as_deref()?, semantic_result)?;
+            function_return_type_with_depth(function, semantic_result, rope, depth - 1)
+        }
+        Expr::Struct(struct_expr) => {
+            let reference_id =
+                semantic.get_reference_at(struct_expr.name.as_ref()?.span.start as usize)?;
+            Some(Type::Struct(semantic.references[reference_id]?))
+        }
+        Expr::Paren(paren_expr) => {
+            infer_expr_type_with_depth(paren_expr.expr.as_deref()?, semantic_result, rope, depth)
+        }
+        Expr::Binary(binary_expr) => {
+            infer_expr_type_with_depth(binary_expr.lhs.as_deref()?, semantic_result, rope, depth)
+        }
+        Expr::Literal(literal_expr) => Some(match literal_expr.value {
+            Literal::Int(_) => Type::Int,
+            Literal::Bool(_) => Type::Bool,
+            Literal::String(_) => Type::String,
+        }),
+    }
+}
+
+/// Resolves the callee of a call expression to the declaration of the function it names.
+pub fn resolve_callee<'a>(
+    callee: &Expr,
+    semantic_result: &'a CompileResult,
+) -> Option<&'a Function> {
+    let Expr::Name(name_expr) = callee else {
+        return None;
+    };
+    let reference_id = semantic_result
+        .semantic
+        .get_reference_at(name_expr.span.start as usize)?;
+    let symbol_id = semantic_result.semantic.references[reference_id]?;
+    if semantic_result.semantic.get_symbol_kind(symbol_id) != SymbolKind::Function {
+        return None;
+    }
+    let name_span = semantic_result.semantic.get_symbol_span(symbol_id);
+    find_function_by_name_span(semantic_result.program.file(), name_span)
+}
+
+/// Infers the type the surrounding code expects at `offset`: a call argument, an annotated
+/// `let` initialiser, a struct literal field or a `return` value.
+pub fn expected_type_at(
+    semantic_result: &CompileResult,
+    rope: &Rope,
+    offset: usize,
+) -> Option<Type> {
+    let file: &File = semantic_result.program.file();
+    let ancestors = ancestors_at(file, offset as u32);
+    for node in ancestors.iter().rev() {
+        match node {
+            SyntaxNode::Expr(Expr::Call(call_expr)) => {
+                let callee = call_expr.callee.as_deref()?;
+                if offset <= callee.span().end as usize {
+                    continue;
+                }
+                let index = call_expr
+                    .args
+                    .iter()
+                    .take_while(|arg| (arg.span().end as usize) < offset)
+                    .count();
+                let function = resolve_callee(callee, semantic_result)?;
+                let param_name = function.params.get(index)?.name.as_ref()?;
+                let symbol_id = semantic_result
+                    .semantic
+                    .get_symbol_at(param_name.span.start as usize)?;
+                return Some(
+                    semantic_result
+                        .semantic
+                        .get_symbol_type(symbol_id)?
+                        .ty
+                        .clone(),
+                );
+            }
+            SyntaxNode::Expr(Expr::Struct(struct_expr)) => {
+                let field_init = struct_expr.fields.iter().find(|field| {
+                    field.span.start as usize <= offset && offset <= field.span.end as usize
+                })?;
+                let field_name = field_init.name.as_ref()?;
+                if offset <= field_name.span.end as usize {
+                    return None;
+                }
+                let reference_id = semantic_result
+                    .semantic
+                    .get_reference_at(struct_expr.name.as_ref()?.span.start as usize)?;
+                let struct_id = semantic_result.semantic.references[reference_id]?;
+                let struct_def = semantic_result.semantic.structs.get(&struct_id)?;
+                let field = struct_def
+                    .fields
+                    .iter()
+                    .find(|field| field.name == field_name.name)?;
+                return Some(field.ty.clone());
+            }
+            SyntaxNode::Stmt(Stmt::Let(let_stmt)) => {
+                let ty = let_stmt.ty.as_ref()?;
+                if offset <= ty.span.end as usize {
+                    return None;
+                }
+                return resolve_type_name(&ty.name, semantic_result, rope);
+            }
+            SyntaxNode::Stmt(Stmt::Return(_)) => {
+                let function = ancestors.iter().find_map(|node| match node {
+                    SyntaxNode::Function(function) => Some(*function),
+                    _ => None,
+                })?;
+                return function_return_type(function, semantic_result, rope);
+            }
+            SyntaxNode::Stmt(_) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use l_lang::compile;
+
+    const SOURCE: &str = "struct Point {\n    x: int,\n    label: string,\n}\n\n\
+                          fn make() -> Point {\n    return Point { x: 1, label: \"a\" };\n}\n\n\
+                          fn wrap() {\n    return make();\n}\n\n\
+                          fn forever() {\n    return forever();\n}\n\n\
+                          fn f(p: Point) {\n    p;\n}\n";
+
+    /// The type inferred for the expression statement `expr`, typed into `f`.
+    fn infer(expr: &str) -> Option<Type> {
+        let source = SOURCE.replace("    p;\n", &format!("    {expr};\n"));
+        let semantic_result = compile(&source);
+        let rope = Rope::from_str(&source);
+        let offset = source.rfind(expr)? as u32;
+        let ancestors = ancestors_at(semantic_result.program.file(), offset);
+        let expr = ancestors.iter().find_map(|node| match node {
+            SyntaxNode::Stmt(Stmt::Expr(expr_stmt)) => expr_stmt.expr.as_deref(),
+            _ => None,
+        })?;
+        infer_expr_type(expr, &semantic_result, &rope)
+    }
+
+    #[test]
+    fn infers_fields_of_calls_and_nested_expressions() {
+        assert_eq!(infer("make().x"), Some(Type::Int));
+        assert_eq!(infer("wrap().label"), Some(Type::String));
+        assert_eq!(infer("(p).x + 1"), Some(Type::Int));
+        assert_eq!(infer("p.missing"), None);
+        assert_eq!(infer("p.x.x"), None);
+    }
+
+    #[test]
+    fn stops_following_calls_that_recurse() {
+        assert_eq!(infer("forever()"), None);
+    }
+
+    #[test]
+    fn finds_the_type_expected_at_an_offset() {
+        let source = "struct Point {\n    x: int,\n    label: string,\n}\n\n\
+                      fn g(a: int, b: bool) -> string {\n    let p: Point = Point { x: a, label: \"\" };\n    \
+                      let n: int = a;\n    g(n, true);\n    return p.label;\n}\n";
+        let semantic_result = compile(source);
+        let rope = Rope::from_str(source);
+        let expected = |marker: &str| {
+            let offset = source.find(marker).unwrap() + marker.len();
+            expected_type_at(&semantic_result, &rope, offset)
+        };
+        assert_eq!(expected("g(n, "), Some(Type::Bool));
+        assert_eq!(expected("    g("), Some(Type::Int));
+        assert_eq!(expected(", label: "), Some(Type::String));
+        assert_eq!(expected("let n: int = "), Some(Type::Int));
+        assert_eq!(expected("return "), Some(Type::String));
+        assert_eq!(expected("let p"), None);
+    }
+}